@@ -1,8 +1,8 @@
-use poise::{CreateReply, serenity_prelude as serenity};
-use rusqlite::{Connection, params};
+use poise::CreateReply;
 use serenity::builder::CreateEmbed;
 
-use super::utils::create_error_embed;
+use super::utils::{create_error_embed, parse_hex_color};
+use crate::data::database::update_user_profile;
 use crate::{Context, Error};
 
 #[poise::command(slash_command, prefix_command, invoke_on_edit, reuse_response)]
@@ -11,39 +11,18 @@ pub async fn color(
 	#[description = "Hex code"] color: String,
 ) -> Result<(), Error> {
 	let user = ctx.author();
-	let user_id = user.id.to_string();
-	let username = &user.name;
 
-	let color = color.replace("#", "");
-
-	if !color.chars().all(|c| c.is_digit(16)) || color.len() != 6 {
+	let Some(color_value) = parse_hex_color(&color) else {
 		let embed =
 			create_error_embed("Invalid hex code. Please provide a valid 6-character hex code.");
 		ctx.send(CreateReply::default().embed(embed)).await?;
 		return Ok(());
-	}
-
-	let color_value = u32::from_str_radix(&color, 16).unwrap();
-	let color_but_with_thingy = format!("0x{}", color);
-
-	{
-		let conn = Connection::open("src/data/users.db")?;
-		let mut stmt = conn.prepare("SELECT COUNT(*) FROM users WHERE id = ?1")?;
-		let mut rows = stmt.query(params![user_id])?;
-		let user_exists = rows.next()?.unwrap().get::<_, i64>(0)? > 0;
+	};
 
-		if user_exists {
-			conn.execute(
-				"UPDATE users SET color = ?1 WHERE id = ?2",
-				params![color_but_with_thingy, user_id],
-			)?;
-		} else {
-			conn.execute(
-				"INSERT INTO users (username, id, color) VALUES (?1, ?2, ?3)",
-				params![*username, user_id, color_but_with_thingy],
-			)?;
-		}
-	}
+	update_user_profile(user.id.get(), &user.name, move |profile| {
+		profile.color = Some(color_value)
+	})
+	.await?;
 
 	let embed = CreateEmbed::default()
 		.title("Color Updated")
@@ -0,0 +1,309 @@
+use futures::stream::{self, StreamExt};
+use poise::CreateReply;
+use serenity::builder::{CreateAttachment, CreateEmbed};
+use tokio::time::Instant;
+
+use crate::commands::gains_utils::{
+	Stat,
+	autocomplete_stat,
+	daily_gains,
+	format_gain,
+	format_gain_days,
+	get_gain_totals,
+	get_snapshots,
+};
+use crate::commands::graph::render_bar_chart;
+use crate::commands::uptime_utils::{MAX_TIME_WINDOW, find_guild};
+use crate::commands::utils::{
+	PRIVATE_PLAYER,
+	create_error_embed,
+	get_account_from_anything,
+	get_color,
+	get_mojang_info,
+	is_private,
+	paginate_embeds,
+	private_uuids,
+};
+use crate::data::tracking::get_tracked_players;
+use crate::tasks::update_gains::update_gains;
+use crate::tasks::update_uptime::ApiError;
+use crate::{Context, ELITE_CLIENT, Error, MONGO_CLIENT};
+
+const LEADERBOARD_SIZE: i64 = 100;
+const LEADERBOARD_PAGE_SIZE: usize = 10;
+
+#[poise::command(
+	slash_command,
+	prefix_command,
+	subcommands("player", "leaderboard"),
+	invoke_on_edit,
+	reuse_response
+)]
+pub async fn gains(
+	ctx: Context<'_>,
+	#[description = "Username, UUID, or discord ID"] player: String,
+	#[description = "Collection or skill, eg wheat or farming"] stat: String,
+	#[description = "Time window, eg 7 for 7 days"] window: Option<i64>,
+) -> Result<(), Error> {
+	player_gains(ctx, player, stat, window).await
+}
+
+/// View a player's daily collection or skill gains
+#[poise::command(slash_command, prefix_command, invoke_on_edit, reuse_response)]
+async fn player(
+	ctx: Context<'_>,
+	#[description = "Username, UUID, or discord ID"] player: String,
+	#[description = "Collection or skill, eg wheat or farming"]
+	#[autocomplete = "autocomplete_stat"]
+	stat: String,
+	#[description = "Time window, eg 7 for 7 days"] window: Option<i64>,
+) -> Result<(), Error> {
+	player_gains(ctx, player, stat, window).await
+}
+
+/// View the tracked players with the highest collection or skill gain
+#[poise::command(slash_command, prefix_command, invoke_on_edit, reuse_response)]
+async fn leaderboard(
+	ctx: Context<'_>,
+	#[description = "Collection or skill, eg wheat or farming"]
+	#[autocomplete = "autocomplete_stat"]
+	stat: String,
+	#[description = "Time window, eg 7 for 7 days"] window: Option<i64>,
+	#[description = "Only include members of this guild, by name or member"] guild: Option<String>,
+) -> Result<(), Error> {
+	let start = Instant::now();
+	ctx.defer().await?;
+
+	let time_window: i64 = window.unwrap_or(7);
+
+	if !(1..=MAX_TIME_WINDOW).contains(&time_window) {
+		let embed = create_error_embed(&format!(
+			"Time window must be between 1 and {} days",
+			MAX_TIME_WINDOW
+		));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let uuids: Vec<String> = match guild {
+		| Some(guild) => match find_guild(&guild).await {
+			| Ok(guild_info) => guild_info.members,
+			| Err(ApiError::NoGuild()) => {
+				let embed = create_error_embed(&format!(
+					"No guild found for `{}`",
+					guild.replace("`", "\\`")
+				));
+				ctx.send(CreateReply::default().embed(embed)).await?;
+				return Ok(());
+			},
+			| Err(e) => {
+				ctx.send(CreateReply::default().embed(create_error_embed(&e.to_string())))
+					.await?;
+				return Ok(());
+			},
+		},
+		| None => {
+			let client = MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
+			get_tracked_players(client)
+				.await?
+				.into_iter()
+				.map(|player| player.uuid)
+				.collect()
+		},
+	};
+
+	let stat = Stat::resolve(&stat);
+	let entries = match get_gain_totals(&stat, time_window, &uuids, Some(LEADERBOARD_SIZE)).await {
+		| Ok(entries) => entries,
+		| Err(e) => {
+			println!("{}", e);
+			let embed = create_error_embed(&e.to_string());
+			ctx.send(CreateReply::default().embed(embed)).await?;
+			return Ok(());
+		},
+	};
+
+	if entries.is_empty() {
+		let embed = create_error_embed(&format!(
+			"No `{}` gains found for this time window",
+			stat.name
+		));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let uuids: Vec<String> = entries.iter().map(|entry| entry.uuid.clone()).collect();
+	let names: Vec<_> = stream::iter(uuids)
+		.map(get_mojang_info)
+		.buffered(10)
+		.collect()
+		.await;
+
+	let hidden = private_uuids(ctx.author().id.get()).await;
+
+	let lines: Vec<String> = entries
+		.iter()
+		.zip(names)
+		.enumerate()
+		.map(|(position, (entry, name))| {
+			let name = if hidden.contains(&entry.uuid) {
+				PRIVATE_PLAYER.to_string()
+			} else {
+				name.map(|(name, _)| name)
+					.unwrap_or_else(|_| entry.uuid.clone())
+					.replace("_", "\\_")
+			};
+			format!(
+				"**{}.** {}: {}",
+				position + 1,
+				name,
+				format_gain(entry.gain)
+			)
+		})
+		.collect();
+	let pages: Vec<String> = lines
+		.chunks(LEADERBOARD_PAGE_SIZE)
+		.map(|page| page.join("\n"))
+		.collect();
+
+	let color = get_color(ctx.author().id.get()).await;
+	println!(
+		"Gains leaderboard command took {} ms",
+		start.elapsed().as_millis()
+	);
+	paginate_embeds(
+		ctx,
+		&format!("{} leaderboard for the last {time_window} days", stat.name),
+		&pages,
+		color,
+	)
+	.await
+}
+
+async fn player_gains(
+	ctx: Context<'_>,
+	player: String,
+	stat: String,
+	window: Option<i64>,
+) -> Result<(), Error> {
+	let start = Instant::now();
+
+	let (defer, account) = tokio::join!(ctx.defer(), get_account_from_anything(&player, None));
+	defer?;
+
+	let (username, uuid) = match account {
+		| Ok(result) => result,
+		| Err(_) => {
+			let embed = create_error_embed("No linked account found");
+			ctx.send(CreateReply::default().embed(embed)).await?;
+			return Ok(());
+		},
+	};
+
+	if is_private(&uuid, ctx.author().id.get()).await {
+		let embed = create_error_embed("This player's stats are private");
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let time_window: i64 = window.unwrap_or(7);
+
+	if !(1..=MAX_TIME_WINDOW).contains(&time_window) {
+		let embed = create_error_embed(&format!(
+			"Time window must be between 1 and {} days",
+			MAX_TIME_WINDOW
+		));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let mut snapshots = get_snapshots(&uuid, time_window).await?;
+	if snapshots.is_empty() {
+		let elite = ELITE_CLIENT.get().expect("ELITE_CLIENT is uninitialized");
+		let client = MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
+		match update_gains(&uuid, elite, client).await {
+			| Ok(true) => snapshots = get_snapshots(&uuid, time_window).await?,
+			| Ok(false) => {
+				let embed = create_error_embed(&format!(
+					"No skyblock profile found for {}",
+					username.replace("_", "\\_")
+				));
+				ctx.send(CreateReply::default().embed(embed)).await?;
+				return Ok(());
+			},
+			| Err(e) => {
+				println!("{}", e);
+				ctx.send(CreateReply::default().embed(create_error_embed(&e.to_string())))
+					.await?;
+				return Ok(());
+			},
+		}
+	}
+
+	let stat = Stat::resolve(&stat);
+	let gains = daily_gains(&snapshots, &stat, time_window);
+	let known: Vec<f64> = gains.iter().filter_map(|(_, gain)| *gain).collect();
+
+	if known.is_empty()
+		&& !snapshots
+			.iter()
+			.any(|snapshot| stat.value(snapshot).is_some())
+	{
+		let embed = create_error_embed(&format!("No data found for `{}`", stat.name));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let total: f64 = known.iter().sum();
+	let average = (!known.is_empty()).then(|| total / known.len() as f64);
+	let best_day = gains
+		.iter()
+		.filter_map(|(day, gain)| gain.map(|gain| (day, gain)))
+		.filter(|(_, gain)| *gain > 0.0)
+		.max_by(|a, b| a.1.total_cmp(&b.1));
+
+	let color = get_color(ctx.author().id.get()).await;
+	let values: Vec<Option<f64>> = gains.iter().map(|(_, gain)| *gain).collect();
+	let graph = match render_bar_chart(&values, "", color) {
+		| Ok(graph) => Some(graph),
+		| Err(e) => {
+			println!(
+				"\x1b[31;1m[ERROR] Failed to render gains graph:\x1b[0m {}",
+				e
+			);
+			None
+		},
+	};
+
+	let mut embed = CreateEmbed::default()
+		.title(format!("{} gains for {username}", stat.name))
+		.description(format_gain_days(&gains))
+		.color(color)
+		.field("Total", format_gain(total), true)
+		.field(
+			"Daily average",
+			average.map_or("Unknown".to_string(), format_gain),
+			true,
+		)
+		.field(
+			"Best day",
+			best_day.map_or("Unknown".to_string(), |(day, gain)| {
+				format!("{} (**{}**)", format_gain(gain), day.format("%Y-%m-%d"))
+			}),
+			true,
+		);
+	let mut reply = CreateReply::default();
+
+	if let Some(graph) = graph {
+		embed = embed.image("attachment://gains.png");
+		reply = reply.attachment(CreateAttachment::bytes(graph, "gains.png"));
+	}
+
+	ctx.send(reply.embed(embed)).await?;
+	println!(
+		"Gains command for {} took {} ms",
+		username,
+		start.elapsed().as_millis()
+	);
+	Ok(())
+}
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, TimeZone};
+use chrono_tz::America::New_York;
+use futures::stream::TryStreamExt;
+use mongodb::bson::doc;
+use serde::Deserialize;
+
+use crate::tasks::update_gains::{GainsSnapshot, day_to_bson, gains_collection};
+use crate::tasks::update_uptime::{ApiError, hypixel_today};
+use crate::{Context, MONGO_CLIENT};
+
+const SKILLS: [&str; 11] = [
+	"farming",
+	"mining",
+	"combat",
+	"foraging",
+	"fishing",
+	"enchanting",
+	"alchemy",
+	"carpentry",
+	"runecrafting",
+	"social",
+	"taming",
+];
+
+/// Display names for collections whose api names don't read well
+const COLLECTION_NAMES: [(&str, &str); 10] = [
+	("Wheat", "WHEAT"),
+	("Carrot", "CARROT_ITEM"),
+	("Potato", "POTATO_ITEM"),
+	("Pumpkin", "PUMPKIN"),
+	("Melon", "MELON"),
+	("Mushroom", "MUSHROOM_COLLECTION"),
+	("Cactus", "CACTUS"),
+	("Sugar Cane", "SUGAR_CANE"),
+	("Nether Wart", "NETHER_STALK"),
+	("Cocoa Beans", "INK_SACK:3"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatKind {
+	Collection,
+	Skill,
+}
+
+/// A collection or skill as stored in `GainsSnapshot`
+#[derive(Debug, Clone)]
+pub struct Stat {
+	pub kind: StatKind,
+	pub key:  String,
+	pub name: String,
+}
+
+impl Stat {
+	/// Accepts skill names, display names of farming collections, or any collection's api name
+	pub fn resolve(input: &str) -> Stat {
+		let input = input.trim();
+
+		if let Some(skill) = SKILLS
+			.iter()
+			.find(|skill| skill.eq_ignore_ascii_case(input))
+		{
+			return Stat {
+				kind: StatKind::Skill,
+				key:  skill.to_string(),
+				name: format!("{}{} xp", skill[..1].to_uppercase(), &skill[1..]),
+			};
+		}
+
+		if let Some((name, key)) = COLLECTION_NAMES
+			.iter()
+			.find(|(name, key)| name.eq_ignore_ascii_case(input) || key.eq_ignore_ascii_case(input))
+		{
+			return Stat {
+				kind: StatKind::Collection,
+				key:  key.to_string(),
+				name: name.to_string(),
+			};
+		}
+
+		Stat {
+			kind: StatKind::Collection,
+			key:  input.to_uppercase().replace(' ', "_"),
+			name: input.to_string(),
+		}
+	}
+
+	pub fn value(
+		&self,
+		snapshot: &GainsSnapshot,
+	) -> Option<f64> {
+		match self.kind {
+			| StatKind::Collection => snapshot
+				.collections
+				.get(&self.key)
+				.map(|&value| value as f64),
+			| StatKind::Skill => snapshot.skills.get(&self.key).copied(),
+		}
+	}
+
+	/// Mongo path of this stat inside a `GainsSnapshot`
+	pub fn field(&self) -> String {
+		match self.kind {
+			| StatKind::Collection => format!("collections.{}", self.key),
+			| StatKind::Skill => format!("skills.{}", self.key),
+		}
+	}
+}
+
+#[derive(Deserialize)]
+pub struct GainTotal {
+	#[serde(rename = "_id")]
+	pub uuid: String,
+	pub gain: f64,
+}
+
+pub async fn autocomplete_stat(
+	_ctx: Context<'_>,
+	partial: &str,
+) -> Vec<String> {
+	let partial = partial.to_lowercase();
+	COLLECTION_NAMES
+		.iter()
+		.map(|(name, _)| name.to_string())
+		.chain(SKILLS.iter().map(|skill| skill.to_string()))
+		.filter(|name| name.to_lowercase().contains(&partial))
+		.collect()
+}
+
+/// Snapshots from the day before the window starts, so the first day's gain is known
+pub async fn get_snapshots(
+	uuid: &str,
+	time_window: i64,
+) -> Result<Vec<GainsSnapshot>, ApiError> {
+	let client = MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
+	let start_date = day_to_bson(hypixel_today() - Duration::days(time_window));
+
+	Ok(gains_collection(client)
+		.find(doc! { "uuid": uuid, "date": { "$gte": start_date } })
+		.await?
+		.try_collect()
+		.await?)
+}
+
+/// Gain of `stat` per player over the window, highest first
+///
+/// Collections and skills only go up, so the gain is the newest value minus the oldest one
+pub async fn get_gain_totals(
+	stat: &Stat,
+	time_window: i64,
+	uuids: &[String],
+	limit: Option<i64>,
+) -> Result<Vec<GainTotal>, ApiError> {
+	let client = MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
+	let start_date = day_to_bson(hypixel_today() - Duration::days(time_window));
+	let field = stat.field();
+	let value = format!("${}", field);
+
+	let mut pipeline = vec![
+		doc! { "$match": {
+			"uuid": { "$in": uuids },
+			"date": { "$gte": start_date },
+			&field: { "$exists": true },
+		} },
+		doc! { "$group": {
+			"_id": "$uuid",
+			"first": { "$min": &value },
+			"last": { "$max": &value },
+		} },
+		doc! { "$project": { "gain": { "$toDouble": { "$subtract": ["$last", "$first"] } } } },
+		doc! { "$match": { "gain": { "$gt": 0 } } },
+		doc! { "$sort": { "gain": -1, "_id": 1 } },
+	];
+	if let Some(limit) = limit {
+		pipeline.push(doc! { "$limit": limit });
+	}
+
+	Ok(gains_collection(client)
+		.aggregate(pipeline)
+		.with_type::<GainTotal>()
+		.await?
+		.try_collect()
+		.await?)
+}
+
+/// Gain of `stat` for each of the last `time_window` days, oldest first
+///
+/// A day is `None` if the snapshot for it or the day before it is missing
+pub fn daily_gains(
+	snapshots: &[GainsSnapshot],
+	stat: &Stat,
+	time_window: i64,
+) -> Vec<(NaiveDate, Option<f64>)> {
+	let values: HashMap<NaiveDate, f64> = snapshots
+		.iter()
+		.filter_map(|snapshot| {
+			let day = New_York
+				.from_utc_datetime(&snapshot.date.to_chrono().naive_utc())
+				.date_naive();
+			stat.value(snapshot).map(|value| (day, value))
+		})
+		.collect();
+
+	let today = hypixel_today();
+	(0..time_window)
+		.rev()
+		.map(|days_ago| {
+			let day = today - Duration::days(days_ago);
+			let gain = values
+				.get(&day)
+				.zip(values.get(&(day - Duration::days(1))))
+				.map(|(current, previous)| (current - previous).max(0.0));
+			(day, gain)
+		})
+		.collect()
+}
+
+/// Format a gain with thousands separators, eg `1,234,567`
+pub fn format_gain(gain: f64) -> String {
+	let digits = (gain.round() as i64).to_string();
+	let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+	for (index, digit) in digits.chars().enumerate() {
+		if index > 0 && (digits.len() - index).is_multiple_of(3) {
+			formatted.push(',');
+		}
+		formatted.push(digit);
+	}
+	formatted
+}
+
+/// One `**day**: gain` line per day, newest first
+pub fn format_gain_days(gains: &[(NaiveDate, Option<f64>)]) -> String {
+	let mut description = String::with_capacity(2_000);
+	for (day, gain) in gains.iter().rev() {
+		let gain = gain.map_or("Unknown".to_string(), format_gain);
+		description.push_str(&format!("**{}**: {}\n", day.format("%Y-%m-%d"), gain));
+	}
+	description
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::commands::uptime_utils::MAX_TIME_WINDOW;
+
+	fn snapshot(
+		days_ago: i64,
+		wheat: i64,
+	) -> GainsSnapshot {
+		GainsSnapshot {
+			id:          None,
+			uuid:        "player1".to_string(),
+			date:        day_to_bson(hypixel_today() - Duration::days(days_ago)),
+			collections: HashMap::from([("WHEAT".to_string(), wheat)]),
+			skills:      HashMap::new(),
+		}
+	}
+
+	#[test]
+	fn gains_are_differences_between_days() {
+		let snapshots = vec![snapshot(3, 100), snapshot(2, 250), snapshot(0, 1_000)];
+		let gains = daily_gains(&snapshots, &Stat::resolve("wheat"), 3);
+
+		let values: Vec<Option<f64>> = gains.into_iter().map(|(_, gain)| gain).collect();
+		// yesterday is missing, so neither it nor today can be calculated
+		assert_eq!(values, vec![Some(150.0), None, None]);
+	}
+
+	#[test]
+	fn resolves_names_case_insensitively() {
+		let wart = Stat::resolve("nether wart");
+		assert_eq!(wart.kind, StatKind::Collection);
+		assert_eq!(wart.key, "NETHER_STALK");
+
+		let farming = Stat::resolve("Farming");
+		assert_eq!(farming.kind, StatKind::Skill);
+		assert_eq!(farming.key, "farming");
+
+		assert_eq!(Stat::resolve("raw fish").key, "RAW_FISH");
+	}
+
+	#[test]
+	fn formats_thousands() {
+		assert_eq!(format_gain(999.0), "999");
+		assert_eq!(format_gain(1_234_567.4), "1,234,567");
+	}
+
+	#[test]
+	fn longest_window_fits_in_the_description() {
+		let gains: Vec<(NaiveDate, Option<f64>)> = (0..MAX_TIME_WINDOW)
+			.map(|days_ago| {
+				(
+					hypixel_today() - Duration::days(days_ago),
+					Some(999_999_999_999.0),
+				)
+			})
+			.collect();
+		assert!(format_gain_days(&gains).chars().count() <= 4096);
+	}
+}
@@ -20,7 +20,7 @@ pub async fn get_linked_account(
 		},
 	};
 
-	let color = get_color(&ctx.author().name);
+	let color = get_color(ctx.author().id.get()).await;
 
 	let embed = CreateEmbed::default()
     .title(format!("Player information for **{username}**"))
@@ -0,0 +1,317 @@
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 400;
+const MARGIN_LEFT: u32 = 52;
+const MARGIN_RIGHT: u32 = 16;
+const MARGIN_TOP: u32 = 16;
+const MARGIN_BOTTOM: u32 = 16;
+
+const BACKGROUND_COLOR: u32 = 0x2b2d31;
+const GRID_COLOR: u32 = 0x3f4147;
+const LABEL_COLOR: u32 = 0xb5bac1;
+const UNKNOWN_COLOR: u32 = 0x4e5058;
+// used when the user's color would be invisible on the background
+const FALLBACK_BAR_COLOR: u32 = 0x5865f2;
+
+const GLYPH_SCALE: u32 = 2;
+/// 3x5 bitmaps for `0`-`9`, `h`, `k` and `m`, read row by row from the most significant bit
+const GLYPHS: [u16; 13] = [
+	0b111_101_101_101_111,
+	0b010_110_010_010_111,
+	0b111_001_111_100_111,
+	0b111_001_111_001_111,
+	0b101_101_111_001_001,
+	0b111_100_111_001_111,
+	0b111_100_111_101_111,
+	0b111_001_001_001_001,
+	0b111_101_111_101_111,
+	0b111_101_111_001_111,
+	0b100_100_111_101_101,
+	0b100_101_110_101_101,
+	0b101_111_111_101_101,
+];
+
+struct Canvas {
+	pixels: Vec<u8>,
+	width:  u32,
+	height: u32,
+}
+
+impl Canvas {
+	fn new(
+		width: u32,
+		height: u32,
+		background: u32,
+	) -> Self {
+		let mut canvas = Canvas {
+			pixels: vec![0; (width * height * 3) as usize],
+			width,
+			height,
+		};
+		canvas.fill_rect(0, 0, width, height, background);
+		canvas
+	}
+
+	fn set_pixel(
+		&mut self,
+		x: u32,
+		y: u32,
+		color: u32,
+	) {
+		if x >= self.width || y >= self.height {
+			return;
+		}
+		let index = ((y * self.width + x) * 3) as usize;
+		self.pixels[index] = (color >> 16) as u8;
+		self.pixels[index + 1] = (color >> 8) as u8;
+		self.pixels[index + 2] = color as u8;
+	}
+
+	fn fill_rect(
+		&mut self,
+		x: u32,
+		y: u32,
+		width: u32,
+		height: u32,
+		color: u32,
+	) {
+		for py in y..y + height {
+			for px in x..x + width {
+				self.set_pixel(px, py, color);
+			}
+		}
+	}
+
+	fn hatch_rect(
+		&mut self,
+		x: u32,
+		y: u32,
+		width: u32,
+		height: u32,
+		color: u32,
+	) {
+		for py in y..y + height {
+			for px in x..x + width {
+				if (px + py) % 8 < 2 {
+					self.set_pixel(px, py, color);
+				}
+			}
+		}
+	}
+
+	fn draw_text(
+		&mut self,
+		x: u32,
+		y: u32,
+		text: &str,
+		color: u32,
+	) {
+		let mut cursor = x;
+		for c in text.chars() {
+			let glyph = match c {
+				| '0'..='9' => GLYPHS[c as usize - '0' as usize],
+				| 'h' => GLYPHS[10],
+				| 'k' => GLYPHS[11],
+				| 'm' => GLYPHS[12],
+				| _ => {
+					cursor += 4 * GLYPH_SCALE;
+					continue;
+				},
+			};
+
+			for row in 0..5 {
+				for column in 0..3 {
+					if glyph >> (14 - (row * 3 + column)) & 1 == 1 {
+						self.fill_rect(
+							cursor + column * GLYPH_SCALE,
+							y + row * GLYPH_SCALE,
+							GLYPH_SCALE,
+							GLYPH_SCALE,
+							color,
+						);
+					}
+				}
+			}
+			cursor += 4 * GLYPH_SCALE;
+		}
+	}
+
+	fn encode(self) -> Result<Vec<u8>, png::EncodingError> {
+		let mut buffer = Vec::new();
+		{
+			let mut encoder = png::Encoder::new(&mut buffer, self.width, self.height);
+			encoder.set_color(png::ColorType::Rgb);
+			encoder.set_depth(png::BitDepth::Eight);
+			let mut writer = encoder.write_header()?;
+			writer.write_image_data(&self.pixels)?;
+			writer.finish()?;
+		}
+		Ok(buffer)
+	}
+}
+
+/// Render `values` as a PNG bar chart, `None` values are drawn as hatched bars
+///
+/// `unit` is appended to the axis labels, it may only use characters in `GLYPHS`
+pub fn render_bar_chart(
+	values: &[Option<f64>],
+	unit: &str,
+	color: u32,
+) -> Result<Vec<u8>, png::EncodingError> {
+	let mut canvas = Canvas::new(WIDTH, HEIGHT, BACKGROUND_COLOR);
+	let bar_color = if color == BACKGROUND_COLOR {
+		FALLBACK_BAR_COLOR
+	} else {
+		color
+	};
+
+	let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+	let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+	let plot_bottom = MARGIN_TOP + plot_height;
+
+	// every bar needs at least a pixel, so long windows are averaged into buckets of days
+	let values = bucket_values(values, plot_width as usize);
+	let max_value = values.iter().flatten().copied().fold(0.0, f64::max);
+	let step = grid_step(max_value);
+	let top = ((max_value / step as f64).ceil() as u64).max(1) * step;
+
+	for line in (0..=top).step_by(step as usize) {
+		let y = plot_bottom - (line * plot_height as u64 / top) as u32;
+		canvas.fill_rect(MARGIN_LEFT, y, plot_width, 1, GRID_COLOR);
+		canvas.draw_text(
+			4,
+			y.saturating_sub(5),
+			&format!("{}{}", format_label(line), unit),
+			LABEL_COLOR,
+		);
+	}
+
+	if values.is_empty() {
+		return canvas.encode();
+	}
+
+	let slot_width = plot_width / values.len() as u32;
+	let bar_width = (slot_width * 7 / 10).max(1);
+
+	for (index, value) in values.iter().enumerate() {
+		let x = MARGIN_LEFT + index as u32 * slot_width + (slot_width - bar_width) / 2;
+		match value {
+			| Some(value) => {
+				let bar_height = ((value / top as f64) * plot_height as f64).round() as u32;
+				canvas.fill_rect(
+					x,
+					plot_bottom - bar_height,
+					bar_width,
+					bar_height,
+					bar_color,
+				);
+			},
+			| None => {
+				canvas.hatch_rect(x, MARGIN_TOP, bar_width, plot_height, UNKNOWN_COLOR);
+			},
+		}
+	}
+
+	canvas.encode()
+}
+
+/// Average consecutive values so there are at most `max_bars`, unknown values are skipped and a
+/// bucket is only unknown if all of its values are
+fn bucket_values(
+	values: &[Option<f64>],
+	max_bars: usize,
+) -> Vec<Option<f64>> {
+	if values.len() <= max_bars {
+		return values.to_vec();
+	}
+
+	values
+		.chunks(values.len().div_ceil(max_bars))
+		.map(|bucket| {
+			let known: Vec<f64> = bucket.iter().flatten().copied().collect();
+			(!known.is_empty()).then(|| known.iter().sum::<f64>() / known.len() as f64)
+		})
+		.collect()
+}
+
+/// Distance between grid lines, keeping at most 6 lines
+fn grid_step(max_value: f64) -> u64 {
+	// hours read best in steps that divide a day
+	if max_value <= 6.0 {
+		1
+	} else if max_value <= 12.0 {
+		2
+	} else if max_value <= 24.0 {
+		4
+	} else {
+		let rough = max_value / 6.0;
+		let magnitude = 10f64.powf(rough.log10().floor());
+		let nice = [1.0, 2.0, 5.0, 10.0]
+			.into_iter()
+			.find(|nice| nice * magnitude >= rough)
+			.unwrap_or(10.0);
+		(nice * magnitude) as u64
+	}
+}
+
+fn format_label(value: u64) -> String {
+	if value >= 1_000_000 && value.is_multiple_of(1_000_000) {
+		format!("{}m", value / 1_000_000)
+	} else if value >= 1_000 && value.is_multiple_of(1_000) {
+		format!("{}k", value / 1_000)
+	} else {
+		value.to_string()
+	}
+}
+
+/// Render per-day uptime oldest to newest, days with unknown gexp (`-1`) are hatched
+pub fn render_uptime_graph(
+	uptime_data: &[(String, i64)],
+	color: u32,
+) -> Result<Vec<u8>, png::EncodingError> {
+	// `YYYY-MM-DD` keys sort chronologically
+	let mut days = uptime_data.to_vec();
+	days.sort_by(|a, b| a.0.cmp(&b.0));
+
+	let hours: Vec<Option<f64>> = days
+		.into_iter()
+		.map(|(_, gexp)| (gexp != -1).then(|| gexp as f64 / 9000.0))
+		.collect();
+
+	render_bar_chart(&hours, "h", color)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+	#[test]
+	fn renders_short_and_oversized_windows() {
+		let week = [
+			Some(2.0),
+			None,
+			Some(5.5),
+			Some(0.0),
+			None,
+			Some(1.0),
+			Some(3.0),
+		];
+		let png = render_bar_chart(&week, "h", 0x2b2d31).unwrap();
+		assert_eq!(png[..8], PNG_SIGNATURE);
+
+		let years: Vec<Option<f64>> = (0..3_000)
+			.map(|day| (day % 7 != 0).then_some(day as f64 % 24.0))
+			.collect();
+		let png = render_bar_chart(&years, "h", 0xff0000).unwrap();
+		assert_eq!(png[..8], PNG_SIGNATURE);
+	}
+
+	#[test]
+	fn buckets_average_known_values() {
+		let values = [Some(1.0), Some(3.0), None, None, Some(4.0)];
+
+		assert_eq!(bucket_values(&values, 5), values.to_vec());
+		assert_eq!(bucket_values(&values, 3), vec![Some(2.0), None, Some(4.0)]);
+	}
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use futures::stream::{self, StreamExt};
+use poise::CreateReply;
+use serenity::builder::{CreateAttachment, CreateEmbed};
+use tokio::time::Instant;
+
+use crate::commands::uptime_utils::{
+	MAX_TIME_WINDOW,
+	find_guild,
+	get_uptime_totals,
+	gexp_to_uptime_as_string,
+};
+use crate::commands::utils::{create_error_embed, get_color, get_mojang_info, paginate_embeds};
+use crate::tasks::update_uptime::ApiError;
+use crate::{Context, Error};
+
+const GUILD_PAGE_SIZE: usize = 20;
+
+#[poise::command(
+	slash_command,
+	prefix_command,
+	subcommands("uptime"),
+	subcommand_required
+)]
+pub async fn guild(_ctx: Context<'_>) -> Result<(), Error> { Ok(()) }
+
+/// View the uptime of every member of a guild
+#[poise::command(slash_command, prefix_command)]
+async fn uptime(
+	ctx: Context<'_>,
+	#[description = "Guild name, or username/uuid of a member"] guild: String,
+	#[description = "Time window, eg 7 for 7 days"]
+	#[min = 1]
+	#[max = 120]
+	window: Option<i64>,
+	#[description = "Flag members with less hours than this"] minimum: Option<f64>,
+	#[description = "Export the table as a CSV file"] csv: Option<bool>,
+) -> Result<(), Error> {
+	let start = Instant::now();
+	ctx.defer().await?;
+
+	let time_window: i64 = window.unwrap_or(7);
+
+	if !(1..=MAX_TIME_WINDOW).contains(&time_window) {
+		let embed = create_error_embed(&format!(
+			"Time window must be between 1 and {} days",
+			MAX_TIME_WINDOW
+		));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let guild_info = match find_guild(&guild).await {
+		| Ok(guild_info) => guild_info,
+		| Err(ApiError::NoGuild()) => {
+			let embed = create_error_embed(&format!(
+				"No guild found for `{}`",
+				guild.replace("`", "\\`")
+			));
+			ctx.send(CreateReply::default().embed(embed)).await?;
+			return Ok(());
+		},
+		| Err(e) => {
+			println!("{}", e);
+			let embed = create_error_embed(&e.to_string());
+			ctx.send(CreateReply::default().embed(embed)).await?;
+			return Ok(());
+		},
+	};
+
+	let totals = match get_uptime_totals(time_window, Some(&guild_info.id), None).await {
+		| Ok(totals) => totals,
+		| Err(e) => {
+			println!("{}", e);
+			let embed = create_error_embed(&e.to_string());
+			ctx.send(CreateReply::default().embed(embed)).await?;
+			return Ok(());
+		},
+	};
+
+	// members without any stored uptime still get listed, they are the ones staff care about
+	let mut gexp_by_uuid: HashMap<String, i64> = totals
+		.into_iter()
+		.map(|total| (total.uuid, total.gexp))
+		.collect();
+	let mut members: Vec<(String, i64)> = guild_info
+		.members
+		.into_iter()
+		.map(|uuid| {
+			let gexp = gexp_by_uuid.remove(&uuid).unwrap_or(0);
+			(uuid, gexp)
+		})
+		.collect();
+	members.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+	let uuids: Vec<String> = members.iter().map(|(uuid, _)| uuid.clone()).collect();
+	let names: Vec<String> = stream::iter(uuids)
+		.map(|uuid| async move {
+			get_mojang_info(uuid.clone())
+				.await
+				.map(|(name, _)| name)
+				.unwrap_or(uuid)
+		})
+		.buffered(10)
+		.collect()
+		.await;
+
+	let minimum_gexp = minimum.map(|hours| (hours * 9000.0) as i64);
+	let is_below_minimum = |gexp: i64| minimum_gexp.is_some_and(|minimum| gexp < minimum);
+	let below_minimum = members
+		.iter()
+		.filter(|(_, gexp)| is_below_minimum(*gexp))
+		.count();
+
+	let color = get_color(ctx.author().id.get()).await;
+	let title = format!(
+		"Uptime for {} over the last {time_window} days",
+		guild_info.name
+	);
+	let summary = match minimum {
+		| Some(minimum) => format!(
+			"{} of {} members are below {}h\n\n",
+			below_minimum,
+			members.len(),
+			minimum
+		),
+		| None => String::new(),
+	};
+
+	if csv.unwrap_or(false) {
+		let mut table = String::from("position,username,uuid,gexp,hours,below_minimum\n");
+		for (position, ((uuid, gexp), name)) in members.iter().zip(&names).enumerate() {
+			table.push_str(&format!(
+				"{},{},{},{},{:.2},{}\n",
+				position + 1,
+				name,
+				uuid,
+				gexp,
+				*gexp as f64 / 9000.0,
+				is_below_minimum(*gexp)
+			));
+		}
+
+		let filename = format!("{}_uptime.csv", guild_info.name.replace(" ", "_"));
+		let embed = CreateEmbed::default()
+			.title(title)
+			.description(format!(
+				"{}Exported {} members to `{}`",
+				summary,
+				members.len(),
+				filename
+			))
+			.color(color);
+		ctx.send(
+			CreateReply::default()
+				.embed(embed)
+				.attachment(CreateAttachment::bytes(table.into_bytes(), filename)),
+		)
+		.await?;
+		return Ok(());
+	}
+
+	let lines: Vec<String> = members
+		.iter()
+		.zip(&names)
+		.enumerate()
+		.map(|(position, ((_, gexp), name))| {
+			format!(
+				"**{}.** {}: {}{}",
+				position + 1,
+				name.replace("_", "\\_"),
+				gexp_to_uptime_as_string(*gexp),
+				if is_below_minimum(*gexp) {
+					" ⚠️"
+				} else {
+					""
+				}
+			)
+		})
+		.collect();
+	let pages: Vec<String> = lines
+		.chunks(GUILD_PAGE_SIZE)
+		.map(|page| format!("{}{}", summary, page.join("\n")))
+		.collect();
+
+	println!(
+		"Guild uptime command for {} took {} ms",
+		guild_info.name,
+		start.elapsed().as_millis()
+	);
+	paginate_embeds(ctx, &title, &pages, color).await
+}
@@ -1,79 +1,306 @@
+use chrono::Utc;
 use poise::CreateReply;
-use rusqlite::{Connection, params};
+use serenity::all::User;
 use serenity::builder::CreateEmbed;
 
 #[allow(deprecated)]
 use crate::commands::utils::{
+	DiscordIdentity,
+	SocialMismatch,
+	check_hypixel_linked_account,
 	create_error_embed,
 	get_account_from_anything_elite,
 	get_color,
-	is_hypixel_linked_account,
+	get_mojang_info,
 };
-use crate::{Context, Error};
+use crate::commands::verify_utils::update_verification;
+use crate::data::database::{
+	LinkAction,
+	LinkAudit,
+	LinkedAccount,
+	add_linked_account,
+	get_linked_accounts,
+	remove_linked_accounts,
+	select_account,
+	set_primary_account,
+};
+use crate::hypixel::rate_limit::Priority;
+use crate::{Context, Error, HYPIXEL_CLIENT};
+
+#[poise::command(
+	slash_command,
+	prefix_command,
+	subcommands("account", "status", "primary", "force", "remove")
+)]
+pub async fn link(
+	ctx: Context<'_>,
+	#[description = "username/uuid"] name: String,
+) -> Result<(), Error> {
+	link_account(ctx, name).await
+}
 
 /// Link your minecraft account to the bot for easier usage
 #[poise::command(slash_command, prefix_command)]
-pub async fn link(
+async fn account(
 	ctx: Context<'_>,
 	#[description = "username/uuid"] name: String,
+) -> Result<(), Error> {
+	link_account(ctx, name).await
+}
+
+/// See which minecraft accounts you have linked
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+async fn status(ctx: Context<'_>) -> Result<(), Error> {
+	let user = ctx.author();
+	let accounts = get_linked_accounts(user.id.get()).await?;
+
+	let Some(primary) = accounts.iter().find(|account| account.primary) else {
+		let embed = create_error_embed("You have not linked an account, use /link account");
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	};
+
+	let embed = CreateEmbed::default()
+		.title("Linked Accounts")
+		.description(format_accounts(&accounts))
+		.color(get_color(user.id.get()).await)
+		.thumbnail(format!(
+			"https://mc-heads.net/body/{}/left",
+			primary.mc_uuid
+		));
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+/// Pick which linked account is used when you don't name one
+#[poise::command(slash_command, prefix_command)]
+async fn primary(
+	ctx: Context<'_>,
+	#[description = "Linked username, uuid or number from /link status"] ign: String,
+) -> Result<(), Error> {
+	let user = ctx.author();
+	let accounts = get_linked_accounts(user.id.get()).await?;
+
+	let Some(account) = select_account(&accounts, &ign) else {
+		let embed = create_error_embed(&format!(
+			"`{}` is not one of your linked accounts, see /link status",
+			ign.replace("`", "\\`")
+		));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	};
+	set_primary_account(user.id.get(), &account.mc_uuid).await?;
+	update_verification(ctx, user.id).await;
+
+	let embed = CreateEmbed::default()
+		.title("Primary Account Updated")
+		.description(format!(
+			"**{}** will be used when you don't pick an account",
+			account.mc_username.replace("_", "\\_")
+		))
+		.color(get_color(user.id.get()).await);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+/// Link a minecraft account to someone without checking their hypixel socials
+#[poise::command(slash_command, prefix_command, owners_only)]
+async fn force(
+	ctx: Context<'_>,
+	#[description = "Discord user to link"] user: User,
+	#[description = "Minecraft username/uuid"] ign: String,
+) -> Result<(), Error> {
+	let (username, uuid) = match get_mojang_info(ign.clone()).await {
+		| Ok(account) => account,
+		| Err(_) => {
+			let embed = create_error_embed(&format!(
+				"No minecraft account found for `{}`",
+				ign.replace("`", "\\`")
+			));
+			ctx.send(CreateReply::default().embed(embed)).await?;
+			return Ok(());
+		},
+	};
+
+	let account = LinkedAccount {
+		user_id:     user.id.get(),
+		mc_uuid:     uuid,
+		mc_username: username,
+		primary:     true,
+		verified_at: None,
+		flag_reason: None,
+	};
+	let audit = LinkAudit {
+		action:   LinkAction::Force,
+		actor_id: ctx.author().id.get(),
+	};
+	let account = add_linked_account(user.id.get(), &user.name, account, true, Some(audit)).await?;
+	update_verification(ctx, user.id).await;
+
+	let embed = CreateEmbed::default()
+		.title("Account Linked")
+		.description(format!(
+			"<@{}> is now linked to **{}**",
+			user.id,
+			account.mc_username.replace("_", "\\_")
+		))
+		.color(get_color(ctx.author().id.get()).await);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+/// Remove someone's linked minecraft accounts
+#[poise::command(slash_command, prefix_command, owners_only)]
+async fn remove(
+	ctx: Context<'_>,
+	#[description = "Discord user to unlink"] user: User,
+	#[description = "Only unlink this account, all are removed if empty"] ign: Option<String>,
+) -> Result<(), Error> {
+	let mc_uuid = match &ign {
+		| Some(ign) => {
+			let accounts = get_linked_accounts(user.id.get()).await?;
+			let Some(account) = select_account(&accounts, ign) else {
+				let embed = create_error_embed(&format!(
+					"<@{}> has not linked `{}`",
+					user.id,
+					ign.replace("`", "\\`")
+				));
+				ctx.send(CreateReply::default().embed(embed)).await?;
+				return Ok(());
+			};
+			Some(account.mc_uuid.clone())
+		},
+		| None => None,
+	};
+
+	let audit = LinkAudit {
+		action:   LinkAction::Remove,
+		actor_id: ctx.author().id.get(),
+	};
+	let removed = remove_linked_accounts(user.id.get(), mc_uuid.as_deref(), Some(audit)).await?;
+	if removed.is_empty() {
+		let embed = create_error_embed(&format!("<@{}> has no linked account", user.id));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+	update_verification(ctx, user.id).await;
+
+	let embed = CreateEmbed::default()
+		.title("Account Unlinked")
+		.description(format!(
+			"<@{}> is no longer linked to {}",
+			user.id,
+			format_names(&removed)
+		))
+		.color(get_color(ctx.author().id.get()).await);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+/// Numbered list of accounts as used by `select_account`, with the primary one marked
+pub fn format_accounts(accounts: &[LinkedAccount]) -> String {
+	accounts
+		.iter()
+		.enumerate()
+		.map(|(index, account)| {
+			let line = format!(
+				"**{}.** {} (`{}`){}",
+				index + 1,
+				account.mc_username.replace("_", "\\_"),
+				account.mc_uuid,
+				if account.primary { " - primary" } else { "" }
+			);
+			match &account.flag_reason {
+				| Some(reason) => format!("{}\n-# Flagged: {}", line, reason),
+				| None => line,
+			}
+		})
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+/// Bold, comma separated igns
+pub fn format_names(accounts: &[LinkedAccount]) -> String {
+	accounts
+		.iter()
+		.map(|account| format!("**{}**", account.mc_username.replace("_", "\\_")))
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+async fn link_account(
+	ctx: Context<'_>,
+	name: String,
 ) -> Result<(), Error> {
 	let user = &ctx.author().name;
-	let user_id = &ctx.author().id.to_string();
 
 	#[allow(deprecated)]
 	match get_account_from_anything_elite(&name).await {
 		| Ok((username, uuid)) => {
-			match is_hypixel_linked_account(uuid.clone(), user.clone()).await {
+			let hypixel = HYPIXEL_CLIENT
+				.get()
+				.expect("HYPIXEL_CLIENT is uninitialized");
+			let identity = DiscordIdentity::from(ctx.author());
+			let mismatch = match check_hypixel_linked_account(
+				hypixel,
+				&uuid,
+				&identity,
+				Priority::Interactive,
+			)
+			.await
+			{
+				| Ok(Ok(())) => None,
+				| Ok(Err(mismatch)) => Some(mismatch),
 				| Err(e) => {
-					ctx.send(
-                        CreateReply::default()
-                            .embed(
-                                create_error_embed(&e.to_string())
-                                    .image("https://media.discordapp.net/attachments/922202066653417512/1066476136953036800/tutorial.gif")
-                            )
-                    )
-                    .await?;
+					ctx.send(CreateReply::default().embed(create_error_embed(&e.to_string())))
+						.await?;
 					return Ok(());
 				},
-				| Ok(false) => {
-					ctx.send(CreateReply::default().embed(create_error_embed(
-						"You cannot link to accounts that are not yours",
-					)))
-					.await?;
-					return Ok(());
-				},
-				| _ => {},
-			}
+			};
 
-			{
-				let conn = Connection::open("src/data/users.db")?;
-
-				let user_exists = {
-					let mut stmt = conn.prepare("SELECT COUNT(*) FROM users WHERE id = ?1")?;
-					let mut rows = stmt.query(params![user_id])?;
-					rows.next()?.unwrap().get::<_, i64>(0)? > 0
+			if let Some(mismatch) = mismatch {
+				let embed = match &mismatch {
+					| SocialMismatch::NotLinked => create_error_embed(&mismatch.to_string()),
+					| SocialMismatch::Different { hypixel, discord } => create_error_embed(
+						"You cannot link to accounts that are not yours. If it is yours, set your discord on Hypixel to match",
+					)
+					.field("Hypixel", format!("`{}`", hypixel.replace("`", "\\`")), true)
+					.field("Discord", format!("`{}`", discord.replace("`", "\\`")), true),
 				};
-
-				if user_exists {
-					conn.execute(
-						"UPDATE users SET mc_username = ?1, mc_uuid = ?2 WHERE id = ?3",
-						params![username, uuid, user_id],
-					)?;
-				} else {
-					conn.execute(
-						"INSERT INTO users (username, id, mc_username, mc_uuid) VALUES (?1, ?2, ?3, ?4)",
-						params![user, user_id, username, uuid],
-					)?;
-				}
+				ctx.send(CreateReply::default().embed(embed.image(
+					"https://media.discordapp.net/attachments/922202066653417512/1066476136953036800/tutorial.gif",
+				)))
+				.await?;
+				return Ok(());
 			}
 
-			let color = get_color(&user);
+			let account = LinkedAccount {
+				user_id:     ctx.author().id.get(),
+				mc_uuid:     uuid,
+				mc_username: username,
+				primary:     false,
+				verified_at: Some(Utc::now().timestamp()),
+				flag_reason: None,
+			};
+			let account =
+				add_linked_account(ctx.author().id.get(), user, account, false, None).await?;
+			update_verification(ctx, ctx.author().id).await;
+
+			let color = get_color(ctx.author().id.get()).await;
+			let description = if account.primary {
+				format!(
+					"Your account, {}, has been linked successfully!",
+					account.mc_username
+				)
+			} else {
+				format!(
+					"Your account, {}, has been linked successfully! Use /link primary to make it your main account",
+					account.mc_username
+				)
+			};
 			let embed = CreateEmbed::default()
 				.title("Account Linked!")
-				.description(format!(
-					"Your account, {}, has been linked successfully!",
-					username
-				))
+				.description(description)
 				.color(color);
 			ctx.send(CreateReply::default().embed(embed)).await?;
 
@@ -2,18 +2,42 @@
 // This file is autogenerated by build.rs, do not edit.
 
 mod color_command;
+mod gains_command;
 mod get_linked_account_command;
+mod guild_command;
 mod link_command;
+mod requirements_command;
+mod settings_command;
+mod stats_command;
+mod track_command;
+mod tracker_command;
+mod unlink_command;
+mod untrack_command;
 mod uptime_command;
+mod verify_command;
+pub mod gains_utils;
+pub mod graph;
 pub mod tags;
+pub mod uptime_utils;
 pub mod utils;
+pub mod verify_utils;
 
 pub fn get_all_commands() -> Vec<poise::Command<crate::Data, crate::types::Error>> {
     vec![
 		color_command::color(), 
+		gains_command::gains(), 
 		get_linked_account_command::get_linked_account(), 
+		guild_command::guild(), 
 		link_command::link(), 
+		requirements_command::requirements(), 
+		settings_command::settings(), 
+		stats_command::stats(), 
 		tags::dtag_command::dtag(), 
 		tags::tag_command::tag(), 
-		uptime_command::uptime()]
+		track_command::track(), 
+		tracker_command::tracker(), 
+		unlink_command::unlink(), 
+		untrack_command::untrack(), 
+		uptime_command::uptime(), 
+		verify_command::verify()]
 }
\ No newline at end of file
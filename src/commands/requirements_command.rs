@@ -0,0 +1,155 @@
+use poise::CreateReply;
+use serenity::all::{CreateEmbed, GuildChannel};
+
+use crate::commands::uptime_utils::find_guild;
+use crate::commands::utils::{create_error_embed, get_color};
+use crate::data::database::{
+	GuildRequirement,
+	get_guild_requirement,
+	remove_guild_requirement,
+	set_guild_requirement,
+};
+use crate::tasks::gexp_requirements::create_requirement_report;
+use crate::tasks::update_uptime::ApiError;
+use crate::{Context, Error};
+
+#[poise::command(
+	slash_command,
+	prefix_command,
+	subcommands("set", "remove", "check"),
+	subcommand_required,
+	guild_only,
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn requirements(_ctx: Context<'_>) -> Result<(), Error> { Ok(()) }
+
+/// Set the weekly gexp requirement for a guild and where to post reports
+#[poise::command(
+	slash_command,
+	prefix_command,
+	guild_only,
+	required_permissions = "MANAGE_GUILD"
+)]
+async fn set(
+	ctx: Context<'_>,
+	#[description = "Guild name, or username/uuid of a member"] guild: String,
+	#[description = "Weekly gexp requirement"] gexp: i64,
+	#[description = "Report channel, defaults to this one"] channel: Option<GuildChannel>,
+) -> Result<(), Error> {
+	ctx.defer().await?;
+
+	if gexp < 1 {
+		let embed = create_error_embed("Requirement must be at least 1 gexp");
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let discord_guild_id = ctx.guild_id().unwrap();
+	if channel
+		.as_ref()
+		.is_some_and(|channel| channel.guild_id != discord_guild_id)
+	{
+		let embed = create_error_embed("The report channel has to be in this server");
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let guild_info = match find_guild(&guild).await {
+		| Ok(guild_info) => guild_info,
+		| Err(ApiError::NoGuild()) => {
+			let embed = create_error_embed(&format!(
+				"No guild found for `{}`",
+				guild.replace("`", "\\`")
+			));
+			ctx.send(CreateReply::default().embed(embed)).await?;
+			return Ok(());
+		},
+		| Err(e) => {
+			let embed = create_error_embed(&e.to_string());
+			ctx.send(CreateReply::default().embed(embed)).await?;
+			return Ok(());
+		},
+	};
+
+	// changing the requirement shouldn't post a report early
+	let last_report = get_guild_requirement(discord_guild_id.get())
+		.await?
+		.map_or(0, |existing| existing.last_report);
+
+	let channel_id = channel.map_or(ctx.channel_id(), |channel| channel.id);
+	let requirement = GuildRequirement {
+		discord_guild_id: discord_guild_id.get(),
+		hypixel_guild_id: guild_info.id,
+		hypixel_guild_name: guild_info.name,
+		gexp,
+		channel_id: channel_id.get(),
+		last_report,
+	};
+	set_guild_requirement(&requirement).await?;
+
+	let color = get_color(ctx.author().id.get()).await;
+	let embed = CreateEmbed::default()
+		.title("Requirement Updated")
+		.description(format!(
+			"Members of **{}** need {} gexp a week, reports will be posted in <#{}>",
+			requirement.hypixel_guild_name, requirement.gexp, requirement.channel_id
+		))
+		.color(color);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+/// Stop checking this server's gexp requirement
+#[poise::command(
+	slash_command,
+	prefix_command,
+	guild_only,
+	required_permissions = "MANAGE_GUILD"
+)]
+async fn remove(ctx: Context<'_>) -> Result<(), Error> {
+	if !remove_guild_requirement(ctx.guild_id().unwrap().get()).await? {
+		let embed = create_error_embed("This server has no gexp requirement set");
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let color = get_color(ctx.author().id.get()).await;
+	let embed = CreateEmbed::default()
+		.title("Requirement Removed")
+		.description("Gexp reports will no longer be posted")
+		.color(color);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+/// Check who is currently below this server's gexp requirement
+#[poise::command(
+	slash_command,
+	prefix_command,
+	guild_only,
+	required_permissions = "MANAGE_GUILD"
+)]
+async fn check(ctx: Context<'_>) -> Result<(), Error> {
+	ctx.defer().await?;
+
+	let Some(requirement) = get_guild_requirement(ctx.guild_id().unwrap().get()).await? else {
+		let embed = create_error_embed(
+			"This server has no gexp requirement set, set one with `/requirements set`",
+		);
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	};
+
+	match create_requirement_report(&requirement).await {
+		| Ok(embed) => {
+			let color = get_color(ctx.author().id.get()).await;
+			ctx.send(CreateReply::default().embed(embed.color(color)))
+				.await?;
+		},
+		| Err(e) => {
+			ctx.send(CreateReply::default().embed(create_error_embed(&e.to_string())))
+				.await?;
+		},
+	}
+	Ok(())
+}
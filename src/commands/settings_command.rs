@@ -0,0 +1,216 @@
+use chrono::Utc;
+use chrono_tz::{TZ_VARIANTS, Tz};
+use poise::CreateReply;
+use serenity::builder::CreateEmbed;
+
+use crate::commands::link_command::format_accounts;
+use crate::commands::uptime_utils::MAX_TIME_WINDOW;
+use crate::commands::utils::{create_error_embed, get_color, parse_hex_color};
+use crate::data::database::{
+	UserProfile,
+	get_linked_accounts,
+	get_user_profile,
+	update_user_profile,
+};
+use crate::{Context, Error};
+
+const DEFAULT_UPTIME_WINDOW: i64 = 7;
+
+#[poise::command(
+	slash_command,
+	prefix_command,
+	subcommands("view", "color", "reset", "timezone", "window", "privacy"),
+	subcommand_required
+)]
+pub async fn settings(_ctx: Context<'_>) -> Result<(), Error> { Ok(()) }
+
+async fn autocomplete_timezone(
+	_ctx: Context<'_>,
+	partial: &str,
+) -> Vec<String> {
+	let partial = partial.to_lowercase().replace(' ', "_");
+	TZ_VARIANTS
+		.iter()
+		.map(|timezone| timezone.name())
+		.filter(|name| name.to_lowercase().contains(&partial))
+		.take(25)
+		.map(ToOwned::to_owned)
+		.collect()
+}
+
+/// Apply `update` to the caller's profile and save it
+async fn update_profile(
+	ctx: Context<'_>,
+	update: impl FnOnce(&mut UserProfile) + Send + 'static,
+) -> Result<UserProfile, Error> {
+	let user = ctx.author();
+	update_user_profile(user.id.get(), &user.name, update).await
+}
+
+async fn send_updated(
+	ctx: Context<'_>,
+	title: &str,
+	description: String,
+) -> Result<(), Error> {
+	let embed = CreateEmbed::default()
+		.title(title)
+		.description(description)
+		.color(get_color(ctx.author().id.get()).await);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+/// View your settings
+#[poise::command(slash_command, prefix_command)]
+async fn view(ctx: Context<'_>) -> Result<(), Error> {
+	let user = ctx.author();
+	let profile = get_user_profile(user.id.get())
+		.await?
+		.unwrap_or_else(|| UserProfile::new(user.id.get(), &user.name));
+
+	let accounts = get_linked_accounts(user.id.get()).await?;
+	let linked = if accounts.is_empty() {
+		"Not linked, use /link".to_string()
+	} else {
+		format_accounts(&accounts)
+	};
+	let window = profile.uptime_window.map_or(
+		format!("{} days (default)", DEFAULT_UPTIME_WINDOW),
+		|window| format!("{} days", window),
+	);
+
+	let embed = CreateEmbed::default()
+		.title(format!("Settings for {}", user.name))
+		.color(get_color(user.id.get()).await)
+		.field("Linked accounts", linked, false)
+		.field(
+			"Color",
+			profile
+				.color
+				.map_or("Default".to_string(), |color| format!("#{:06x}", color)),
+			true,
+		)
+		.field(
+			"Timezone",
+			profile
+				.timezone
+				.map_or("Hypixel (America/New_York)".to_string(), |tz| {
+					tz.name().to_string()
+				}),
+			true,
+		)
+		.field("Uptime window", window, true)
+		.field(
+			"Stats",
+			if profile.private { "Private" } else { "Public" },
+			true,
+		);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+/// Set the color of your embeds
+#[poise::command(slash_command, prefix_command)]
+async fn color(
+	ctx: Context<'_>,
+	#[description = "Hex code"] color: String,
+) -> Result<(), Error> {
+	let Some(color) = parse_hex_color(&color) else {
+		let embed =
+			create_error_embed("Invalid hex code. Please provide a valid 6-character hex code.");
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	};
+
+	update_profile(ctx, move |profile| profile.color = Some(color)).await?;
+	send_updated(
+		ctx,
+		"Color Updated",
+		format!("Your embeds will now use **#{:06x}**", color),
+	)
+	.await
+}
+
+/// Reset all of your settings, your linked accounts are kept
+#[poise::command(slash_command, prefix_command)]
+async fn reset(ctx: Context<'_>) -> Result<(), Error> {
+	update_profile(ctx, UserProfile::reset_settings).await?;
+	send_updated(
+		ctx,
+		"Settings Reset",
+		"Your settings have been reset to their defaults".to_string(),
+	)
+	.await
+}
+
+/// Set the timezone dates are shown in
+#[poise::command(slash_command, prefix_command)]
+async fn timezone(
+	ctx: Context<'_>,
+	#[description = "IANA timezone, eg Europe/Berlin"]
+	#[autocomplete = "autocomplete_timezone"]
+	timezone: String,
+) -> Result<(), Error> {
+	let Ok(tz) = timezone.trim().parse::<Tz>() else {
+		let embed = create_error_embed(&format!(
+			"`{}` is not a valid timezone, pick one from the list, eg `Europe/Berlin`",
+			timezone.replace("`", "\\`")
+		));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	};
+
+	update_profile(ctx, move |profile| profile.timezone = Some(tz)).await?;
+	send_updated(
+		ctx,
+		"Timezone Updated",
+		format!(
+			"Dates will be shown for **{}**, it is currently {} there",
+			tz.name(),
+			Utc::now().with_timezone(&tz).format("%H:%M")
+		),
+	)
+	.await
+}
+
+/// Set how many days /uptime shows when no window is given
+#[poise::command(slash_command, prefix_command)]
+async fn window(
+	ctx: Context<'_>,
+	#[description = "Time window, eg 7 for 7 days"]
+	#[min = 1]
+	#[max = 120]
+	days: i64,
+) -> Result<(), Error> {
+	if !(1..=MAX_TIME_WINDOW).contains(&days) {
+		let embed = create_error_embed(&format!(
+			"Time window must be between 1 and {} days",
+			MAX_TIME_WINDOW
+		));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	update_profile(ctx, move |profile| profile.uptime_window = Some(days)).await?;
+	send_updated(
+		ctx,
+		"Uptime Window Updated",
+		format!("/uptime will show the last **{}** days by default", days),
+	)
+	.await
+}
+
+/// Hide your uptime and gains from other users
+#[poise::command(slash_command, prefix_command)]
+async fn privacy(
+	ctx: Context<'_>,
+	#[description = "Hide your stats from lookups and leaderboards"] private: bool,
+) -> Result<(), Error> {
+	update_profile(ctx, move |profile| profile.private = private).await?;
+	let description = if private {
+		"Only you can look up the stats of your linked account, and leaderboards hide your name. Guild uptime and requirement reports still list you for your guild's staff"
+	} else {
+		"Everyone can see the stats of your linked account"
+	};
+	send_updated(ctx, "Privacy Updated", description.to_string()).await
+}
@@ -0,0 +1,37 @@
+use poise::CreateReply;
+use serenity::builder::CreateEmbed;
+
+use crate::commands::utils::get_color;
+use crate::data::tracking::{get_tracked_guilds, get_tracked_players};
+use crate::tasks::tracker_status::tracker_status;
+use crate::{Context, Error, MONGO_CLIENT};
+
+/// Show how many players, guilds and tags the bot keeps track of
+#[poise::command(slash_command, prefix_command)]
+pub async fn stats(ctx: Context<'_>) -> Result<(), Error> {
+	ctx.defer().await?;
+
+	let client = MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
+	let tracked_players = get_tracked_players(client).await?.len();
+	let tracked_guilds = get_tracked_guilds(client).await?.len();
+	let (tag_servers, tags) = ctx.data().tag_db.count_tags().await?;
+
+	// the tracker also follows every member of guilds it fetches, not only subscriptions
+	let status = tracker_status();
+
+	let color = get_color(ctx.author().id.get()).await;
+	let embed = CreateEmbed::default()
+		.title("Stats")
+		.field("Tracked players", tracked_players.to_string(), true)
+		.field("Tracked guilds", tracked_guilds.to_string(), true)
+		.field(
+			"Players updated last cycle",
+			status.players_processed.to_string(),
+			true,
+		)
+		.field("Tags", tags.to_string(), true)
+		.field("Servers with tags", tag_servers.to_string(), true)
+		.color(color);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
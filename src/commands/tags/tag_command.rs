@@ -136,7 +136,7 @@ async fn edit(
 async fn list(ctx: Context<'_>) -> Result<(), Error> {
 	let (data, id) = get_data_and_id(ctx).await?;
 
-	let color = get_color(&ctx.author().name);
+	let color = get_color(ctx.author().id.get()).await;
 
 	match data.tag_db.get_all_tags(id).await {
 		| Ok(tags) => {
@@ -2,12 +2,13 @@ use std::fmt;
 
 use poise::CreateReply;
 use rayon::prelude::*;
+use rusqlite::params;
 use strsim::jaro_winkler;
 use tokio::task;
 
 use crate::commands::utils::create_error_embed;
 use crate::types::{Context, Error};
-use crate::{DB_POOL, Data, ExpectError};
+use crate::{DB_POOL, Data};
 
 pub struct TagDb;
 
@@ -21,29 +22,14 @@ impl TagDb {
 		guild_id: u64,
 	) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 		let pool = DB_POOL.get().unwrap();
-		let conn = pool.get().expect_error("Failed to get database connection");
-
-		let table_name = format!("tags_{}", guild_id);
-
-		conn.execute(
-			&format!(
-				"CREATE TABLE IF NOT EXISTS {} (
-                    name TEXT PRIMARY KEY,
-                    content TEXT NOT NULL
-                )",
-				table_name
-			),
-			[],
-		)?;
-
 		let name = name.to_string();
 		let content = content.to_string();
 
 		task::spawn_blocking(move || {
 			let conn = pool.get()?;
 			conn.execute(
-				&format!("INSERT INTO {} (name, content) VALUES (?1, ?2)", table_name),
-				[&name, &content],
+				"INSERT INTO tags (guild_id, name, content) VALUES (?1, ?2, ?3)",
+				params![guild_id as i64, name, content],
 			)?;
 			Ok(())
 		})
@@ -58,14 +44,12 @@ impl TagDb {
 		let pool = DB_POOL.get().unwrap();
 
 		if let Some((fixed_name, _)) = fix_typos(name, guild_id).await? {
-			let table_name = format!("tags_{}", guild_id);
-
 			let result = task::spawn_blocking(
 				move || -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
 					let conn = pool.get()?;
 					let modified = conn.execute(
-						&format!("DELETE FROM {} WHERE name = ?1", table_name),
-						[&fixed_name],
+						"DELETE FROM tags WHERE guild_id = ?1 AND name = ?2",
+						params![guild_id as i64, fixed_name],
 					)?;
 					if modified != 0 {
 						Ok(Some(fixed_name))
@@ -91,15 +75,14 @@ impl TagDb {
 		let pool = DB_POOL.get().unwrap();
 
 		if let Some((fixed_name, _)) = fix_typos(name, guild_id).await? {
-			let table_name = format!("tags_{}", guild_id);
 			let content = content.to_string();
 
 			let result = task::spawn_blocking(
 				move || -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
 					let conn = pool.get()?;
 					let modified = conn.execute(
-						&format!("UPDATE {} SET content = ?1 WHERE name = ?2", table_name),
-						[&content, &fixed_name],
+						"UPDATE tags SET content = ?1 WHERE guild_id = ?2 AND name = ?3",
+						params![content, guild_id as i64, fixed_name],
 					)?;
 					if modified != 0 {
 						Ok(Some(fixed_name))
@@ -122,16 +105,13 @@ impl TagDb {
 		guild_id: u64,
 	) -> Result<Option<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
 		let pool = DB_POOL.get().unwrap();
-		let table_name = format!("tags_{}", guild_id);
 		let name = name.to_string();
 
 		task::spawn_blocking(move || {
 			let conn = pool.get()?;
-			let mut stmt = conn.prepare(&format!(
-				"SELECT name, content FROM {} WHERE name = ?1",
-				table_name
-			))?;
-			let mut rows = stmt.query([name])?;
+			let mut stmt =
+				conn.prepare("SELECT name, content FROM tags WHERE guild_id = ?1 AND name = ?2")?;
+			let mut rows = stmt.query(params![guild_id as i64, name])?;
 
 			if let Some(row) = rows.next()? {
 				Ok(Some((row.get(0)?, row.get(1)?)))
@@ -158,12 +138,11 @@ impl TagDb {
 		guild_id: u64,
 	) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
 		let pool = DB_POOL.get().unwrap();
-		let table_name = format!("tags_{}", guild_id);
 
 		task::spawn_blocking(move || {
 			let conn = pool.get()?;
-			let mut stmt = conn.prepare(&format!("SELECT name FROM {}", table_name))?;
-			let rows = stmt.query_map([], |row| row.get(0))?;
+			let mut stmt = conn.prepare("SELECT name FROM tags WHERE guild_id = ?1")?;
+			let rows = stmt.query_map(params![guild_id as i64], |row| row.get(0))?;
 
 			let mut tags = Vec::new();
 			for tag in rows {
@@ -174,6 +153,25 @@ impl TagDb {
 		})
 		.await?
 	}
+
+	/// Returns how many servers have tags and how many tags there are in total
+	pub async fn count_tags(
+		&self
+	) -> Result<(usize, i64), Box<dyn std::error::Error + Send + Sync>> {
+		let pool = DB_POOL.get().unwrap();
+
+		task::spawn_blocking(move || {
+			let conn = pool.get()?;
+			let (servers, tags): (i64, i64) = conn.query_row(
+				"SELECT COUNT(DISTINCT guild_id), COUNT(*) FROM tags",
+				[],
+				|row| Ok((row.get(0)?, row.get(1)?)),
+			)?;
+
+			Ok((servers as usize, tags))
+		})
+		.await?
+	}
 }
 
 #[derive(Debug)]
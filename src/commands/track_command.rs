@@ -0,0 +1,204 @@
+use bson::DateTime as BsonDateTime;
+use poise::CreateReply;
+use serenity::builder::CreateEmbed;
+
+use crate::commands::uptime_utils::find_guild;
+use crate::commands::utils::{create_error_embed, get_color, get_mojang_info, paginate_embeds};
+use crate::data::tracking::{
+	MAX_TRACKING_DAYS,
+	TrackedGuild,
+	TrackedPlayer,
+	expiry_from_days,
+	get_tracked_guilds,
+	get_tracked_players,
+	track_guild,
+	track_player,
+};
+use crate::tasks::update_uptime::ApiError;
+use crate::{Context, Error, MONGO_CLIENT};
+
+const TRACKED_PAGE_SIZE: usize = 20;
+
+#[poise::command(
+	slash_command,
+	prefix_command,
+	subcommands("player", "guild", "list"),
+	subcommand_required,
+	owners_only
+)]
+pub async fn track(_ctx: Context<'_>) -> Result<(), Error> { Ok(()) }
+
+/// Start tracking a player's uptime
+#[poise::command(slash_command, prefix_command, owners_only)]
+async fn player(
+	ctx: Context<'_>,
+	#[description = "Username or UUID"] player: String,
+	#[description = "Why this player is tracked"] reason: Option<String>,
+	#[description = "Stop tracking after this many days"] days: Option<i64>,
+) -> Result<(), Error> {
+	if days.is_some_and(|days| !(1..=MAX_TRACKING_DAYS).contains(&days)) {
+		let embed = create_error_embed(&format!(
+			"Tracking must last between 1 and {} days, leave it empty to track forever",
+			MAX_TRACKING_DAYS
+		));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let (username, uuid) = match get_mojang_info(player).await {
+		| Ok(result) => result,
+		| Err(e) => {
+			ctx.send(CreateReply::default().embed(create_error_embed(&e.to_string())))
+				.await?;
+			return Ok(());
+		},
+	};
+
+	let client = MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
+	let tracked = TrackedPlayer {
+		id: None,
+		uuid,
+		reason: reason.unwrap_or("No reason given".to_string()),
+		requested_by: ctx.author().id.to_string(),
+		added_at: BsonDateTime::now(),
+		expires_at: expiry_from_days(days),
+	};
+	track_player(client, &tracked).await?;
+
+	let color = get_color(ctx.author().id.get()).await;
+	let embed = CreateEmbed::default()
+		.title("Player Tracked")
+		.description(format!(
+			"Now tracking **{}**{}",
+			username.replace("_", "\\_"),
+			format_expiry(&tracked.expires_at)
+		))
+		.color(color);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+/// Start tracking the uptime of every member of a guild
+#[poise::command(slash_command, prefix_command, owners_only)]
+async fn guild(
+	ctx: Context<'_>,
+	#[description = "Guild name, or username/uuid of a member"] guild: String,
+	#[description = "Why this guild is tracked"] reason: Option<String>,
+	#[description = "Stop tracking after this many days"] days: Option<i64>,
+) -> Result<(), Error> {
+	ctx.defer().await?;
+
+	if days.is_some_and(|days| !(1..=MAX_TRACKING_DAYS).contains(&days)) {
+		let embed = create_error_embed(&format!(
+			"Tracking must last between 1 and {} days, leave it empty to track forever",
+			MAX_TRACKING_DAYS
+		));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let guild_info = match find_guild(&guild).await {
+		| Ok(guild_info) => guild_info,
+		| Err(ApiError::NoGuild()) => {
+			let embed = create_error_embed(&format!(
+				"No guild found for `{}`",
+				guild.replace("`", "\\`")
+			));
+			ctx.send(CreateReply::default().embed(embed)).await?;
+			return Ok(());
+		},
+		| Err(e) => {
+			ctx.send(CreateReply::default().embed(create_error_embed(&e.to_string())))
+				.await?;
+			return Ok(());
+		},
+	};
+
+	let client = MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
+	let tracked = TrackedGuild {
+		id:           None,
+		guild_id:     guild_info.id,
+		name:         guild_info.name,
+		reason:       reason.unwrap_or("No reason given".to_string()),
+		requested_by: ctx.author().id.to_string(),
+		added_at:     BsonDateTime::now(),
+		expires_at:   expiry_from_days(days),
+	};
+	track_guild(client, &tracked).await?;
+
+	let color = get_color(ctx.author().id.get()).await;
+	let embed = CreateEmbed::default()
+		.title("Guild Tracked")
+		.description(format!(
+			"Now tracking **{}** and its {} members{}",
+			tracked.name,
+			guild_info.members.len(),
+			format_expiry(&tracked.expires_at)
+		))
+		.color(color);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+/// List every tracked player and guild
+#[poise::command(slash_command, prefix_command, owners_only)]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+	ctx.defer().await?;
+
+	let client = MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
+	let guilds = get_tracked_guilds(client).await?;
+	let players = get_tracked_players(client).await?;
+
+	let lines: Vec<String> = guilds
+		.iter()
+		.map(|guild| {
+			format!(
+				"🛡️ **{}** - {} (<@{}>){}",
+				guild.name,
+				guild.reason,
+				guild.requested_by,
+				format_expiry(&guild.expires_at)
+			)
+		})
+		.chain(players.iter().map(|player| {
+			format!(
+				"👤 `{}` - {} (<@{}>){}",
+				player.uuid,
+				player.reason,
+				player.requested_by,
+				format_expiry(&player.expires_at)
+			)
+		}))
+		.collect();
+
+	if lines.is_empty() {
+		let embed = create_error_embed("Nothing is being tracked");
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let pages: Vec<String> = lines
+		.chunks(TRACKED_PAGE_SIZE)
+		.map(|page| page.join("\n"))
+		.collect();
+
+	let color = get_color(ctx.author().id.get()).await;
+	paginate_embeds(
+		ctx,
+		&format!(
+			"Tracking {} guilds and {} players",
+			guilds.len(),
+			players.len()
+		),
+		&pages,
+		color,
+	)
+	.await
+}
+
+fn format_expiry(expires_at: &Option<BsonDateTime>) -> String {
+	match expires_at {
+		| Some(date) => format!(", expires <t:{}:R>", date.timestamp_millis() / 1000),
+		| None => String::new(),
+	}
+}
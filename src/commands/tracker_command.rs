@@ -0,0 +1,71 @@
+use chrono::{DateTime, Utc};
+use poise::CreateReply;
+use serenity::builder::CreateEmbed;
+
+use crate::commands::utils::get_color;
+use crate::tasks::tracker_status::tracker_status;
+use crate::{Context, Error};
+
+#[poise::command(
+	slash_command,
+	prefix_command,
+	subcommands("status"),
+	subcommand_required,
+	owners_only
+)]
+pub async fn tracker(_ctx: Context<'_>) -> Result<(), Error> { Ok(()) }
+
+/// Show what the uptime tracker is currently doing
+#[poise::command(slash_command, prefix_command, owners_only)]
+async fn status(ctx: Context<'_>) -> Result<(), Error> {
+	let status = tracker_status();
+
+	let state = if status.running {
+		format!("Running cycle {}", status.cycle)
+	} else if status.cycle == 0 {
+		"Starting up".to_string()
+	} else {
+		format!("Idle after cycle {}", status.cycle)
+	};
+
+	let failures = if status.failures.is_empty() {
+		"None".to_string()
+	} else {
+		status
+			.failures
+			.iter()
+			.map(|(kind, count)| format!("{}: {}", kind, count))
+			.collect::<Vec<String>>()
+			.join("\n")
+	};
+
+	let color = get_color(ctx.author().id.get()).await;
+	let embed = CreateEmbed::default()
+		.title("Uptime Tracker")
+		.description(state)
+		.field(
+			"Players",
+			format!("{} / {}", status.players_processed, status.players_total),
+			true,
+		)
+		.field(
+			"Guilds",
+			format!("{} / {}", status.guilds_processed, status.guilds_total),
+			true,
+		)
+		.field("Requests", status.requests.to_string(), true)
+		.field("Cycle started", format_time(status.cycle_started), true)
+		.field("Last success", format_time(status.last_success), true)
+		.field("Next run", format_time(status.next_run), true)
+		.field("Failures", failures, false)
+		.color(color);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+fn format_time(time: Option<DateTime<Utc>>) -> String {
+	match time {
+		| Some(time) => format!("<t:{}:R>", time.timestamp()),
+		| None => "Never".to_string(),
+	}
+}
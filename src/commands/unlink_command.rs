@@ -0,0 +1,48 @@
+use poise::CreateReply;
+use serenity::builder::CreateEmbed;
+
+use crate::commands::link_command::format_names;
+use crate::commands::utils::{create_error_embed, get_color};
+use crate::commands::verify_utils::update_verification;
+use crate::data::database::{get_linked_accounts, remove_linked_accounts, select_account};
+use crate::{Context, Error};
+
+/// Unlink your minecraft accounts from the bot
+#[poise::command(slash_command, prefix_command)]
+pub async fn unlink(
+	ctx: Context<'_>,
+	#[description = "Only unlink this account, all are removed if empty"] ign: Option<String>,
+) -> Result<(), Error> {
+	let user = ctx.author();
+
+	let mc_uuid = match &ign {
+		| Some(ign) => {
+			let accounts = get_linked_accounts(user.id.get()).await?;
+			let Some(account) = select_account(&accounts, ign) else {
+				let embed = create_error_embed(&format!(
+					"`{}` is not one of your linked accounts, see /link status",
+					ign.replace("`", "\\`")
+				));
+				ctx.send(CreateReply::default().embed(embed)).await?;
+				return Ok(());
+			};
+			Some(account.mc_uuid.clone())
+		},
+		| None => None,
+	};
+
+	let removed = remove_linked_accounts(user.id.get(), mc_uuid.as_deref(), None).await?;
+	if removed.is_empty() {
+		let embed = create_error_embed("You have not linked an account");
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+	update_verification(ctx, user.id).await;
+
+	let embed = CreateEmbed::default()
+		.title("Account Unlinked")
+		.description(format!("Unlinked {}", format_names(&removed)))
+		.color(get_color(user.id.get()).await);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
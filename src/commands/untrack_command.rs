@@ -0,0 +1,86 @@
+use poise::CreateReply;
+use serenity::builder::CreateEmbed;
+
+use crate::commands::utils::{create_error_embed, get_color, get_mojang_info};
+use crate::data::tracking::{get_tracked_guilds, untrack_guild, untrack_player};
+use crate::{Context, Error, MONGO_CLIENT};
+
+#[poise::command(
+	slash_command,
+	prefix_command,
+	subcommands("player", "guild"),
+	subcommand_required,
+	owners_only
+)]
+pub async fn untrack(_ctx: Context<'_>) -> Result<(), Error> { Ok(()) }
+
+/// Stop tracking a player's uptime
+#[poise::command(slash_command, prefix_command, owners_only)]
+async fn player(
+	ctx: Context<'_>,
+	#[description = "Username or UUID"] player: String,
+) -> Result<(), Error> {
+	let (username, uuid) = match get_mojang_info(player).await {
+		| Ok(result) => result,
+		| Err(e) => {
+			ctx.send(CreateReply::default().embed(create_error_embed(&e.to_string())))
+				.await?;
+			return Ok(());
+		},
+	};
+
+	let client = MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
+	if !untrack_player(client, &uuid).await? {
+		let embed = create_error_embed(&format!(
+			"**{}** is not being tracked",
+			username.replace("_", "\\_")
+		));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let color = get_color(ctx.author().id.get()).await;
+	let embed = CreateEmbed::default()
+		.title("Player Untracked")
+		.description(format!(
+			"No longer tracking **{}**",
+			username.replace("_", "\\_")
+		))
+		.color(color);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+/// Stop tracking a guild
+#[poise::command(slash_command, prefix_command, owners_only)]
+async fn guild(
+	ctx: Context<'_>,
+	#[description = "Tracked guild name"] guild: String,
+) -> Result<(), Error> {
+	let client = MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
+
+	// match against tracked guilds so disbanded guilds can still be removed
+	let tracked = get_tracked_guilds(client)
+		.await?
+		.into_iter()
+		.find(|tracked| tracked.name.eq_ignore_ascii_case(&guild) || tracked.guild_id == guild);
+
+	let Some(tracked) = tracked else {
+		let embed = create_error_embed(&format!(
+			"`{}` is not being tracked",
+			guild.replace("`", "\\`")
+		));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	};
+
+	untrack_guild(client, &tracked.guild_id).await?;
+
+	let color = get_color(ctx.author().id.get()).await;
+	let embed = CreateEmbed::default()
+		.title("Guild Untracked")
+		.description(format!("No longer tracking **{}**", tracked.name))
+		.color(color);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
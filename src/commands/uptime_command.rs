@@ -1,51 +1,211 @@
 use std::collections::HashMap;
 use std::pin::Pin;
 
-use bson::DateTime as BsonDateTime;
-use chrono::{Duration, TimeZone, Utc};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
 use chrono_tz::America::New_York;
-use futures::stream::StreamExt;
+use chrono_tz::Tz;
+use futures::stream::{self, StreamExt};
 use mongodb::bson::doc;
 use mongodb::{Client, Cursor};
 use poise::CreateReply;
-use serenity::builder::CreateEmbed;
+use serenity::builder::{CreateAttachment, CreateEmbed, CreateEmbedFooter};
 use tokio::time::Instant;
 
-use crate::commands::utils::{create_error_embed, get_account_from_anything, get_color};
-use crate::tasks::update_uptime::{ApiError, Uptime, update_uptime};
-use crate::{API_KEY, Context, Error, MONGO_CLIENT};
+use crate::commands::graph::render_uptime_graph;
+use crate::commands::uptime_utils::{MAX_TIME_WINDOW, get_uptime_totals, gexp_to_uptime_as_string};
+use crate::commands::utils::{
+	PRIVATE_PLAYER,
+	create_error_embed,
+	get_account_from_anything,
+	get_color,
+	get_mojang_info,
+	is_private,
+	paginate_embeds,
+	private_uuids,
+};
+use crate::data::database::get_user_profile;
+use crate::data::tracking::is_player_tracked;
+use crate::hypixel::rate_limit::Priority;
+use crate::tasks::update_uptime::{
+	ApiError,
+	Uptime,
+	day_key,
+	hypixel_today,
+	update_uptime,
+	uptime_collection,
+};
+use crate::{Context, Error, HYPIXEL_CLIENT, MONGO_CLIENT};
 
-#[poise::command(slash_command, prefix_command, invoke_on_edit, reuse_response)]
+const LEADERBOARD_SIZE: i64 = 100;
+const LEADERBOARD_PAGE_SIZE: usize = 10;
+
+#[poise::command(
+	slash_command,
+	prefix_command,
+	subcommands("player", "leaderboard"),
+	invoke_on_edit,
+	reuse_response
+)]
 pub async fn uptime(
 	ctx: Context<'_>,
 	#[description = "Username, UUID, or discord ID"] user: Option<String>,
 	#[description = "Time window, eg 7 for 7 days"] window: Option<i64>,
+	#[description = "Which linked account, by ign or number"] account: Option<String>,
+) -> Result<(), Error> {
+	player_uptime(ctx, user, window, account).await
+}
+
+/// View a player's uptime
+#[poise::command(slash_command, prefix_command, invoke_on_edit, reuse_response)]
+async fn player(
+	ctx: Context<'_>,
+	#[description = "Username, UUID, or discord ID"] user: Option<String>,
+	#[description = "Time window, eg 7 for 7 days"] window: Option<i64>,
+	#[description = "Which linked account, by ign or number"] account: Option<String>,
+) -> Result<(), Error> {
+	player_uptime(ctx, user, window, account).await
+}
+
+/// View the tracked players with the highest uptime
+#[poise::command(slash_command, prefix_command, invoke_on_edit, reuse_response)]
+async fn leaderboard(
+	ctx: Context<'_>,
+	#[description = "Time window, eg 7 for 7 days"] window: Option<i64>,
+	#[description = "Only include members of this guild ID"] guild: Option<String>,
+) -> Result<(), Error> {
+	let start = Instant::now();
+	ctx.defer().await?;
+
+	let time_window: i64 = window.unwrap_or(7);
+
+	if !(1..=MAX_TIME_WINDOW).contains(&time_window) {
+		let embed = create_error_embed(&format!(
+			"Time window must be between 1 and {} days",
+			MAX_TIME_WINDOW
+		));
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let entries =
+		match get_uptime_totals(time_window, guild.as_deref(), Some(LEADERBOARD_SIZE)).await {
+			| Ok(entries) => entries,
+			| Err(e) => {
+				println!("{}", e);
+				let embed = create_error_embed(&e.to_string());
+				ctx.send(CreateReply::default().embed(embed)).await?;
+				return Ok(());
+			},
+		};
+
+	if entries.is_empty() {
+		let embed = create_error_embed("No uptime data found for this time window");
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let uuids: Vec<String> = entries.iter().map(|entry| entry.uuid.clone()).collect();
+	let names: Vec<_> = stream::iter(uuids)
+		.map(get_mojang_info)
+		.buffered(10)
+		.collect()
+		.await;
+
+	let hidden = private_uuids(ctx.author().id.get()).await;
+
+	let lines: Vec<String> = entries
+		.iter()
+		.zip(names)
+		.enumerate()
+		.map(|(position, (entry, name))| {
+			let name = if hidden.contains(&entry.uuid) {
+				PRIVATE_PLAYER.to_string()
+			} else {
+				name.map(|(name, _)| name)
+					.unwrap_or_else(|_| entry.uuid.clone())
+					.replace("_", "\\_")
+			};
+			format!(
+				"**{}.** {}: {}",
+				position + 1,
+				name,
+				gexp_to_uptime_as_string(entry.gexp)
+			)
+		})
+		.collect();
+	let pages: Vec<String> = lines
+		.chunks(LEADERBOARD_PAGE_SIZE)
+		.map(|page| page.join("\n"))
+		.collect();
+
+	let color = get_color(ctx.author().id.get()).await;
+	println!(
+		"Uptime leaderboard command took {} ms",
+		start.elapsed().as_millis()
+	);
+	paginate_embeds(
+		ctx,
+		&format!("Uptime leaderboard for the last {time_window} days"),
+		&pages,
+		color,
+	)
+	.await
+}
+
+async fn player_uptime(
+	ctx: Context<'_>,
+	user: Option<String>,
+	window: Option<i64>,
+	alt: Option<String>,
 ) -> Result<(), Error> {
 	let start = Instant::now();
 
 	let (defer, account) = tokio::join!(ctx.defer(), async {
 		let user_id = user.unwrap_or_else(|| ctx.author().id.to_string());
-		get_account_from_anything(&user_id).await
+		get_account_from_anything(&user_id, alt.as_deref()).await
 	});
 	defer?;
 
 	let (username, uuid) = match account {
 		| Ok(result) => result,
-		| Err(_) => {
-			let embed = create_error_embed("No linked account found");
+		| Err(e) => {
+			let embed = create_error_embed(&e.to_string());
 			ctx.send(CreateReply::default().embed(embed)).await?;
 			return Ok(());
 		},
 	};
-	let time_window: i64 = window.unwrap_or(7);
 
-	if time_window < 1 {
-		let embed = create_error_embed("Time window must be at least 1 day");
+	let viewer = ctx.author().id.get();
+	if is_private(&uuid, viewer).await {
+		let embed = create_error_embed("This player's stats are private");
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let profile = get_user_profile(viewer).await.ok().flatten();
+	let time_window: i64 = window
+		// defaults saved before the cap was lowered would otherwise be rejected every time
+		.or(profile
+			.as_ref()
+			.and_then(|profile| profile.uptime_window)
+			.map(|days| days.min(MAX_TIME_WINDOW)))
+		.unwrap_or(7);
+
+	if !(1..=MAX_TIME_WINDOW).contains(&time_window) {
+		let embed = create_error_embed(&format!(
+			"Time window must be between 1 and {} days",
+			MAX_TIME_WINDOW
+		));
 		ctx.send(CreateReply::default().embed(embed)).await?;
 		return Ok(());
 	}
 
-	let mut uptime_data = match get_uptime(&uuid, time_window).await {
+	let timezone = profile.and_then(|profile| profile.timezone);
+	let today = window_end(timezone, Utc::now());
+
+	// fetch twice the window so it can be compared against the previous one
+	let start_day = day_key(today - Duration::days(time_window * 2 - 1));
+	let uptime_data = match get_uptime(&uuid, start_day).await {
 		| Ok(uptime_data) => uptime_data,
 		| Err(e) => {
 			println!("{}", e);
@@ -55,27 +215,64 @@ pub async fn uptime(
 		},
 	};
 
-	if uptime_data.len() < time_window as usize {
-		uptime_data = fill_missing_dates(uptime_data, time_window);
-	}
+	let mut uptime_data = fill_missing_dates(uptime_data, time_window * 2, today);
+	let previous_data = uptime_data.split_off(time_window as usize);
+	let stats = calculate_stats(&uptime_data);
+	let previous_stats = calculate_stats(&previous_data);
 
-	let mut description = String::with_capacity(2_000);
-	for (date, gexp) in uptime_data {
-		let uptime = if gexp == -1 {
-			"Unknown".to_string()
-		} else {
-			gexp_to_uptime_as_string(gexp)
-		};
-		description.push_str(&format!("{}: {}\n", BsonDateTime_to_string(&date), uptime));
-	}
+	let color = get_color(ctx.author().id.get()).await;
+	let graph = match render_uptime_graph(&uptime_data, color) {
+		| Ok(graph) => Some(graph),
+		| Err(e) => {
+			println!(
+				"\x1b[31;1m[ERROR] Failed to render uptime graph:\x1b[0m {}",
+				e
+			);
+			None
+		},
+	};
 
-	let color = get_color(&ctx.author().name);
-	let embed = CreateEmbed::default()
+	let mut embed = CreateEmbed::default()
 		.title(format!("Uptime for {username}"))
-		.description(description)
-		.color(color);
+		.description(format_uptime_days(&uptime_data))
+		.color(color)
+		.field("Total", gexp_to_uptime_as_string(stats.total), true)
+		.field(
+			"Daily average",
+			stats
+				.average
+				.map_or("Unknown".to_string(), gexp_to_uptime_as_string),
+			true,
+		)
+		.field(
+			"Best day",
+			stats
+				.best_day
+				.as_ref()
+				.map_or("Unknown".to_string(), |(day, gexp)| {
+					format!("{} (**{}**)", gexp_to_uptime_as_string(*gexp), day)
+				}),
+			true,
+		)
+		.field(
+			"Longest streak",
+			format!("{} days", stats.longest_streak),
+			true,
+		)
+		.field(
+			format!("vs previous {time_window} days"),
+			compare_stats(&stats, &previous_stats),
+			true,
+		)
+		.footer(CreateEmbedFooter::new(day_reset_note(timezone)));
+	let mut reply = CreateReply::default();
+
+	if let Some(graph) = graph {
+		embed = embed.image("attachment://uptime.png");
+		reply = reply.attachment(CreateAttachment::bytes(graph, "uptime.png"));
+	}
 
-	ctx.send(CreateReply::default().embed(embed)).await?;
+	ctx.send(reply.embed(embed)).await?;
 	println!(
 		"Uptime command for {} took {} ms",
 		username,
@@ -86,47 +283,52 @@ pub async fn uptime(
 
 fn get_uptime(
 	uuid: &str,
-	time_window: i64,
-) -> Pin<Box<dyn Future<Output = Result<Vec<(BsonDateTime, i64)>, ApiError>> + Send + '_>> {
+	start_day: String,
+) -> Pin<Box<dyn Future<Output = Result<Vec<(String, i64)>, ApiError>> + Send + '_>> {
 	Box::pin(async move {
-		let date = New_York.from_utc_datetime(&Utc::now().naive_utc());
-		let start_date = BsonDateTime::from_chrono(date - Duration::days(time_window));
 		let filter = doc! {
 			"uuid": uuid,
-			"date": { "$gte": start_date }
+			"day": { "$gte": start_day }
 		};
 
 		let client: &Client = &MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
 
-		let mut cursor: Cursor<Uptime> = client
-			.database("Players")
-			.collection("Uptime")
-			.find(filter.clone())
-			.await?;
+		let mut cursor: Cursor<Uptime> = uptime_collection(client).find(filter.clone()).await?;
 		let mut results = Vec::new();
 
 		while let Some(result) = cursor.next().await {
 			let playtime = result?;
-			results.push((playtime.date, playtime.gexp as i64));
+			results.push((playtime.day, playtime.gexp));
 		}
 
-		if results.is_empty() {
-			update_uptime(
+		// the updater only keeps tracked players current, anyone else is refreshed when looked up
+		let tracked = is_player_tracked(client, uuid).await?;
+		if needs_refresh(&results, tracked) {
+			let refreshed = update_uptime(
 				uuid,
-				API_KEY.get().expect("API_KEY is uninitialized"),
+				HYPIXEL_CLIENT
+					.get()
+					.expect("HYPIXEL_CLIENT is uninitialized"),
 				&client,
+				Priority::Interactive,
 			)
-			.await?;
-
-			let mut cursor: Cursor<Uptime> = client
-				.database("Players")
-				.collection("Uptime")
-				.find(filter)
-				.await?;
-			results.clear();
-			while let Some(result) = cursor.next().await {
-				let playtime = result?;
-				results.push((playtime.date, playtime.gexp as i64));
+			.await;
+
+			match refreshed {
+				| Ok(()) => {
+					let mut cursor: Cursor<Uptime> = uptime_collection(client).find(filter).await?;
+					results.clear();
+					while let Some(result) = cursor.next().await {
+						let playtime = result?;
+						results.push((playtime.day, playtime.gexp));
+					}
+				},
+				| Err(e) if results.is_empty() => return Err(e),
+				// stored days are still worth showing, eg after the player left their guild
+				| Err(e) => println!(
+					"\x1b[31;1m[ERROR] Failed to refresh uptime of {}:\x1b[0m {}",
+					uuid, e
+				),
 			}
 		}
 
@@ -134,53 +336,217 @@ fn get_uptime(
 	})
 }
 
+/// Whether stored uptime is missing or out of date, untracked players are never kept current
+fn needs_refresh(
+	results: &[(String, i64)],
+	tracked: bool,
+) -> bool {
+	let today = day_key(hypixel_today());
+	!tracked || !results.iter().any(|(day, _)| *day >= today)
+}
+
+/// Newest Hypixel day to show, uptime is stored per Hypixel day and the caller's timezone only
+/// decides which day is today. Ahead of New York the local date hasn't started on Hypixel yet
+fn window_end(
+	timezone: Option<Tz>,
+	now: DateTime<Utc>,
+) -> NaiveDate {
+	let hypixel_today = now.with_timezone(&New_York).date_naive();
+	timezone.map_or(hypixel_today, |tz| {
+		now.with_timezone(&tz).date_naive().min(hypixel_today)
+	})
+}
+
+/// Newest day first, ending at `today`, days without a document get `-1`
 fn fill_missing_dates(
-	results: Vec<(BsonDateTime, i64)>,
+	results: Vec<(String, i64)>,
 	time_window: i64,
-) -> Vec<(BsonDateTime, i64)> {
-	let now = New_York
-		.from_utc_datetime(&Utc::now().naive_utc())
-		.date_naive();
-	let start_date = now - Duration::days(time_window - 1);
-
-	let date_map: HashMap<_, _> = results
-		.into_iter()
-		.map(|(date, gexp)| {
-			let est_date = New_York
-				.from_utc_datetime(&date.to_chrono().naive_utc())
-				.date_naive();
-			(est_date, gexp)
+	today: NaiveDate,
+) -> Vec<(String, i64)> {
+	let day_map: HashMap<String, i64> = results.into_iter().collect();
+
+	(0..time_window)
+		.map(|days_ago| {
+			let day = day_key(today - Duration::days(days_ago));
+			let gexp = day_map.get(&day).copied().unwrap_or(-1);
+			(day, gexp)
 		})
-		.collect();
+		.collect()
+}
+
+/// Dates are Hypixel days, which reset at midnight Eastern. The reset is shown in the caller's
+/// timezone when it is known, a Hypixel day spans two local dates so they can't be converted
+fn day_reset_note(timezone: Option<Tz>) -> String {
+	let Some(tz) = timezone else {
+		return "Dates are Hypixel days, they reset at midnight Eastern. Set your timezone with /settings timezone"
+			.to_string();
+	};
+
+	let next_reset = (hypixel_today() + Duration::days(1))
+		.and_hms_opt(0, 0, 0)
+		.and_then(|midnight| New_York.from_local_datetime(&midnight).earliest());
+
+	match next_reset {
+		| Some(reset) => format!(
+			"Dates are Hypixel days, they reset at midnight Eastern ({} in {})",
+			reset.with_timezone(&tz).format("%H:%M"),
+			tz.name()
+		),
+		| None => "Dates are Hypixel days, they reset at midnight Eastern".to_string(),
+	}
+}
+
+struct UptimeStats {
+	days:           i64,
+	total:          i64,
+	known_days:     i64,
+	average:        Option<i64>,
+	best_day:       Option<(String, i64)>,
+	longest_streak: usize,
+}
 
-	let mut filled_results = Vec::with_capacity(time_window as usize);
+/// Expects `uptime_data` to be contiguous days, unknown days (`-1`) are skipped and break streaks
+fn calculate_stats(uptime_data: &[(String, i64)]) -> UptimeStats {
+	let known: Vec<_> = uptime_data.iter().filter(|(_, gexp)| *gexp != -1).collect();
+
+	let total = known.iter().map(|(_, gexp)| gexp).sum();
+	let known_days = known.len() as i64;
+	let average = (known_days > 0).then(|| total / known_days);
+	let best_day = known
+		.iter()
+		.filter(|(_, gexp)| *gexp > 0)
+		.max_by_key(|(_, gexp)| *gexp)
+		.map(|(day, gexp)| (day.clone(), *gexp));
+
+	let mut longest_streak = 0;
+	let mut current_streak = 0;
+	for (_, gexp) in uptime_data {
+		if *gexp > 0 {
+			current_streak += 1;
+			longest_streak = longest_streak.max(current_streak);
+		} else {
+			current_streak = 0;
+		}
+	}
 
-	for days in 0..time_window {
-		let current_date = start_date + Duration::days(days);
-		let datetime_est = current_date.and_hms_opt(0, 0, 0).unwrap();
-		let datetime_utc = New_York
-			.from_local_datetime(&datetime_est)
-			.unwrap()
-			.naive_utc();
-		let bson_date = BsonDateTime::from_chrono(Utc.from_utc_datetime(&datetime_utc));
+	UptimeStats {
+		days: uptime_data.len() as i64,
+		total,
+		known_days,
+		average,
+		best_day,
+		longest_streak,
+	}
+}
 
-		let gexp = date_map.get(&current_date).copied().unwrap_or(-1);
-		filled_results.push((bson_date, gexp));
+fn compare_stats(
+	current: &UptimeStats,
+	previous: &UptimeStats,
+) -> String {
+	if current.known_days == 0 || previous.known_days == 0 {
+		return "Unknown".to_string();
 	}
 
-	filled_results.sort_by(|a, b| b.0.cmp(&a.0));
+	// totals are only comparable when every day is known, otherwise compare the daily averages
+	let complete = current.known_days == current.days && previous.known_days == previous.days;
+	let (current_gexp, previous_gexp) = if complete {
+		(current.total, previous.total)
+	} else {
+		(
+			current.total / current.known_days,
+			previous.total / previous.known_days,
+		)
+	};
+
+	let difference = current_gexp - previous_gexp;
+	let sign = if difference < 0 { "-" } else { "+" };
+	let mut comparison = format!("{}{}", sign, gexp_to_uptime_as_string(difference.abs()));
+
+	if previous_gexp > 0 {
+		comparison.push_str(&format!(
+			" ({}{}%)",
+			sign,
+			difference.abs() * 100 / previous_gexp
+		));
+	}
+	if !complete {
+		comparison.push_str(" per day, some days unknown");
+	}
 
-	filled_results
+	comparison
 }
 
-fn gexp_to_uptime_as_string(gexp: i64) -> String {
-	format!("{}h {}m", gexp / 9000, (gexp % 9000) / 150)
+/// One `**day**: uptime` line per day
+fn format_uptime_days(uptime_data: &[(String, i64)]) -> String {
+	let mut description = String::with_capacity(2_000);
+	for (day, gexp) in uptime_data {
+		let uptime = if *gexp == -1 {
+			"Unknown".to_string()
+		} else {
+			gexp_to_uptime_as_string(*gexp)
+		};
+		description.push_str(&format!("**{}**: {}\n", day, uptime));
+	}
+	description
 }
 
-#[allow(non_snake_case)]
-fn BsonDateTime_to_string(date: &BsonDateTime) -> String {
-	format!(
-		"**{}**",
-		date.to_string().get(..10).unwrap_or("Unknown Date")
-	)
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn days(gexp: &[i64]) -> Vec<(String, i64)> {
+		gexp.iter()
+			.enumerate()
+			.map(|(day, gexp)| (format!("2024-01-{:02}", day + 1), *gexp))
+			.collect()
+	}
+
+	#[test]
+	fn compares_totals_only_when_every_day_is_known() {
+		let current = calculate_stats(&days(&[9000, 9000]));
+		let previous = calculate_stats(&days(&[9000, 0]));
+		assert_eq!(compare_stats(&current, &previous), "+1h 0m (+100%)");
+
+		// one unknown day would otherwise look like a 50% drop
+		let previous = calculate_stats(&days(&[18000, -1]));
+		assert_eq!(
+			compare_stats(&current, &previous),
+			"-1h 0m (-50%) per day, some days unknown"
+		);
+	}
+
+	#[test]
+	fn longest_window_fits_in_the_description() {
+		// a full day of gexp is the longest uptime string
+		let description = format_uptime_days(&days(&vec![215_850; MAX_TIME_WINDOW as usize]));
+		assert!(description.chars().count() <= 4096);
+	}
+
+	#[test]
+	fn refreshes_untracked_and_outdated_uptime() {
+		let today = day_key(hypixel_today());
+		let yesterday = day_key(hypixel_today() - Duration::days(1));
+
+		assert!(!needs_refresh(&[(today.clone(), 150)], true));
+		assert!(needs_refresh(&[(today, 150)], false));
+		assert!(needs_refresh(&[(yesterday, 150)], true));
+		assert!(needs_refresh(&[], true));
+	}
+
+	#[test]
+	fn window_never_ends_on_a_future_hypixel_day() {
+		// 23:00 in New York, already the next day in Berlin
+		let now = "2025-03-02T04:00:00Z".parse::<DateTime<Utc>>().unwrap();
+		let march_first = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+
+		assert_eq!(window_end(None, now), march_first);
+		assert_eq!(
+			window_end(Some(chrono_tz::Europe::Berlin), now),
+			march_first
+		);
+		assert_eq!(
+			window_end(Some(chrono_tz::America::Los_Angeles), now),
+			march_first
+		);
+	}
 }
@@ -0,0 +1,99 @@
+use futures::stream::StreamExt;
+use mongodb::bson::doc;
+use serde::Deserialize;
+
+use crate::commands::utils::get_mojang_info;
+use crate::hypixel::client::GuildLookup;
+use crate::hypixel::rate_limit::Priority;
+use crate::tasks::update_uptime::{
+	ApiError,
+	GuildInfo,
+	update_guild_uptime,
+	uptime_collection,
+	window_start_day,
+};
+use crate::{HYPIXEL_CLIENT, MONGO_CLIENT};
+
+/// longest time window in days the uptime and gains commands accept, every day gets a line in
+/// the embed description which can't be longer than 4096 characters
+pub const MAX_TIME_WINDOW: i64 = 120;
+
+#[derive(Deserialize)]
+pub struct UptimeTotal {
+	#[serde(rename = "_id")]
+	pub uuid: String,
+	pub gexp: i64,
+}
+
+/// Total gexp per player over the window, highest first
+pub async fn get_uptime_totals(
+	time_window: i64,
+	guild_id: Option<&str>,
+	limit: Option<i64>,
+) -> Result<Vec<UptimeTotal>, ApiError> {
+	let mut filter = doc! {
+		"day": { "$gte": window_start_day(time_window) }
+	};
+	if let Some(guild_id) = guild_id {
+		filter.insert("guild_id", guild_id);
+	}
+
+	let mut pipeline = vec![
+		doc! { "$match": filter },
+		doc! { "$group": { "_id": "$uuid", "gexp": { "$sum": "$gexp" } } },
+		doc! { "$sort": { "gexp": -1, "_id": 1 } },
+	];
+	if let Some(limit) = limit {
+		pipeline.push(doc! { "$limit": limit });
+	}
+
+	let client = MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
+
+	let mut cursor = uptime_collection(client)
+		.aggregate(pipeline)
+		.with_type::<UptimeTotal>()
+		.await?;
+	let mut entries = Vec::new();
+
+	while let Some(entry) = cursor.next().await {
+		entries.push(entry?);
+	}
+
+	Ok(entries)
+}
+
+/// Look up a guild by name, falling back to the guild of a player with that name or uuid
+pub async fn find_guild(query: &str) -> Result<GuildInfo, ApiError> {
+	let hypixel = HYPIXEL_CLIENT
+		.get()
+		.expect("HYPIXEL_CLIENT is uninitialized");
+	let client = MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
+
+	match update_guild_uptime(
+		GuildLookup::Name(query),
+		hypixel,
+		client,
+		Priority::Interactive,
+	)
+	.await
+	{
+		| Err(ApiError::NoGuild()) => {},
+		| result => return result,
+	}
+
+	let (_, uuid) = get_mojang_info(query.to_string())
+		.await
+		.map_err(|_| ApiError::NoGuild())?;
+
+	update_guild_uptime(
+		GuildLookup::Player(&uuid),
+		hypixel,
+		client,
+		Priority::Interactive,
+	)
+	.await
+}
+
+pub fn gexp_to_uptime_as_string(gexp: i64) -> String {
+	format!("{}h {}m", gexp / 9000, (gexp % 9000) / 150)
+}
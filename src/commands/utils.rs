@@ -1,33 +1,81 @@
-use rusqlite::{Connection, Result, params};
+use std::collections::HashSet;
+use std::fmt;
+use std::time::Duration;
+
+use poise::CreateReply;
+use rusqlite::{OptionalExtension, params};
 use serde::Deserialize;
-use serenity::all::CreateEmbed;
+use serenity::all::{
+	ComponentInteractionCollector,
+	CreateActionRow,
+	CreateButton,
+	CreateEmbed,
+	CreateEmbedFooter,
+	CreateInteractionResponse,
+	CreateInteractionResponseMessage,
+	User,
+};
 
 use crate::ERROR_COLOR;
-use crate::tasks::update_uptime::ApiError;
-use crate::types::Error;
-
-fn get_color_backend(username: &str) -> Result<Option<String>> {
-	let conn = Connection::open("src/data/users.db")?;
-	let mut stmt = conn.prepare("SELECT color FROM users WHERE username = ?1")?;
-	let mut rows = stmt.query(params![username])?;
+use crate::data::database::{
+	get_linked_accounts,
+	get_private_uuids,
+	get_user_profile_by_uuid,
+	select_account,
+	with_users_db,
+};
+use crate::hypixel::client::HypixelClient;
+use crate::hypixel::rate_limit::Priority;
+use crate::hypixel::responses::Links;
+use crate::types::{Context, Error};
+
+/// Embed color a discord user picked with `/settings color`
+pub async fn get_color(user_id: u64) -> u32 {
+	let color: Option<String> = with_users_db(move |conn| {
+		conn.query_row(
+			"SELECT color FROM users WHERE id = ?1",
+			params![user_id as i64],
+			|row| row.get(0),
+		)
+		.optional()
+	})
+	.await
+	.ok()
+	.flatten()
+	.flatten();
+
+	color
+		.and_then(|color| u32::from_str_radix(color.trim_start_matches("0x"), 16).ok())
+		.unwrap_or(0x2b2d31) // color of discord embed with default discord dark theme
+}
 
-	if let Some(row) = rows.next()? {
-		let color: String = row.get(0)?;
-		Ok(Some(color))
-	} else {
-		Ok(None)
+/// Parse a 6 character hex code, with or without a leading `#`
+pub fn parse_hex_color(color: &str) -> Option<u32> {
+	let color = color.trim().trim_start_matches('#');
+	if color.len() != 6 || !color.chars().all(|c| c.is_ascii_hexdigit()) {
+		return None;
 	}
+	u32::from_str_radix(color, 16).ok()
 }
 
-pub fn get_color(username: &str) -> u32 {
-	// TODO: combine these 2 functions into 1
-	let color_result = get_color_backend(username);
-
-	color_result
+/// Whether the discord user linked to `uuid` hides their stats from `viewer`
+pub async fn is_private(
+	uuid: &str,
+	viewer: u64,
+) -> bool {
+	get_user_profile_by_uuid(uuid)
+		.await
 		.ok()
 		.flatten()
-		.and_then(|color_str| u32::from_str_radix(color_str.trim_start_matches("0x"), 16).ok())
-		.unwrap_or(0x2b2d31) // color of discord embed with default discord dark theme
+		.is_some_and(|profile| profile.hides_stats_from(viewer))
+}
+
+/// Shown on leaderboards in place of players who hide their stats
+pub const PRIVATE_PLAYER: &str = "*Private player*";
+
+/// Uuids `viewer` shouldn't see the names of on leaderboards
+pub async fn private_uuids(viewer: u64) -> HashSet<String> {
+	get_private_uuids(viewer).await.unwrap_or_default()
 }
 
 #[derive(Deserialize)]
@@ -46,7 +94,7 @@ pub async fn get_account_from_anything_elite(identifier: &str) -> Result<(String
 	let result = if identifier.len() == 32 || identifier.len() <= 16 {
 		get_mojang_info(identifier.to_string()).await?
 	} else if clean_identifier.parse::<u64>().is_ok() {
-		match get_account_from_anything(&clean_identifier).await {
+		match get_account_from_anything(&clean_identifier, None).await {
 			| Ok(result) => return Ok(result),
 			| Err(_) => get_linked_elite_account(clean_identifier).await?,
 		}
@@ -60,7 +108,12 @@ pub async fn get_account_from_anything_elite(identifier: &str) -> Result<(String
 	Ok(result)
 }
 
-pub async fn get_account_from_anything(identifier: &str) -> Result<(String, String), Error> {
+/// Resolve a username, uuid, or discord user to `(username, uuid)`.
+/// For discord users `alt` picks one of their linked accounts, otherwise the primary one is used
+pub async fn get_account_from_anything(
+	identifier: &str,
+	alt: Option<&str>,
+) -> Result<(String, String), Error> {
 	let clean_identifier = identifier
 		.replace(&['@', '<', '>'][..], "")
 		.trim()
@@ -69,7 +122,7 @@ pub async fn get_account_from_anything(identifier: &str) -> Result<(String, Stri
 	let result = if identifier.len() == 32 || identifier.len() <= 16 {
 		get_mojang_info(identifier.to_string()).await?
 	} else if clean_identifier.parse::<u64>().is_ok() {
-		get_linked_account(clean_identifier).await?
+		get_linked_account(clean_identifier, alt).await?
 	} else {
 		return Err(Box::new(std::io::Error::new(
 			std::io::ErrorKind::InvalidInput,
@@ -80,20 +133,34 @@ pub async fn get_account_from_anything(identifier: &str) -> Result<(String, Stri
 	Ok(result)
 }
 
-pub async fn get_linked_account(id: String) -> Result<(String, String), Error> {
-	let conn = Connection::open("src/data/users.db")?;
+pub async fn get_linked_account(
+	id: String,
+	alt: Option<&str>,
+) -> Result<(String, String), Error> {
+	let accounts = match id.parse::<u64>() {
+		| Ok(id) => get_linked_accounts(id).await?,
+		| Err(_) => Vec::new(),
+	};
 
-	let mut stmt = conn.prepare("SELECT mc_username, mc_uuid FROM users WHERE id = ?1")?;
-	let mut rows = stmt.query([id])?;
+	let account = match alt {
+		| Some(alt) => select_account(&accounts, alt),
+		| None => accounts.iter().find(|account| account.primary),
+	};
 
-	if let Some(row) = rows.next()? {
-		Ok((row.get(0)?, row.get(1)?))
-	} else {
-		Err(Box::new(std::io::Error::new(
-			std::io::ErrorKind::InvalidInput,
-			"No linked account found",
-		)))
-	}
+	account
+		.map(|account| (account.mc_username.clone(), account.mc_uuid.clone()))
+		.ok_or_else(|| {
+			let message = match alt {
+				| Some(alt) if !accounts.is_empty() => {
+					format!("No linked account matches `{}`", alt.replace("`", "\\`"))
+				},
+				| _ => "No linked account found".to_string(),
+			};
+			Box::new(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				message,
+			)) as Error
+		})
 }
 
 pub async fn get_mojang_info(player: String) -> Result<(String, String), Error> {
@@ -153,100 +220,149 @@ pub async fn get_linked_elite_account(discord_id: String) -> Result<(String, Str
 	Ok((account.name, account.id))
 }
 
-#[derive(Deserialize, Debug)]
-struct PlayerResponse {
-	success: bool,
-	player:  Option<Player>,
-	#[serde(default)]
-	cause:   Option<String>,
+pub async fn get_hypixel_linked_socials(
+	hypixel: &HypixelClient,
+	uuid: &str,
+	priority: Priority,
+) -> Result<Links, Error> {
+	let player = hypixel.get_player(uuid, priority).await?;
+
+	Ok(player
+		.and_then(|player| player.social_media)
+		.map(|social_media| social_media.links)
+		.unwrap_or_default())
 }
 
-#[derive(Deserialize, Debug)]
-#[allow(non_snake_case)]
-struct Player {
-	socialMedia: Option<SocialMedia>,
+/// The names a discord user can have put in their hypixel socials
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscordIdentity {
+	pub username:      String,
+	/// only set for accounts that haven't moved to unique usernames yet
+	pub discriminator: Option<u16>,
+	pub global_name:   Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
-struct SocialMedia {
-	links: Links,
-}
+impl DiscordIdentity {
+	/// An identity known only by its username, eg one stored in `users.db`
+	pub fn from_username(username: &str) -> Self {
+		DiscordIdentity {
+			username:      username.to_string(),
+			discriminator: None,
+			global_name:   None,
+		}
+	}
 
-#[derive(Deserialize, Debug)]
-#[allow(non_snake_case, dead_code)]
-pub struct Links {
-	TWITTER:   Option<String>,
-	YOUTUBE:   Option<String>,
-	INSTAGRAM: Option<String>,
-	TWITCH:    Option<String>,
-	DISCORD:   Option<String>,
-	FORUMS:    Option<String>,
+	/// Whether `linked`, as set on hypixel, belongs to this user. The username or display name
+	/// are accepted ignoring case, as is the username with the legacy `name#1234` tag hypixel
+	/// still has for anyone who linked before the move to unique usernames
+	pub fn matches(
+		&self,
+		linked: &str,
+	) -> bool {
+		let linked = linked.trim();
+
+		let (name, discriminator) = match linked.rsplit_once('#') {
+			| Some((name, tag))
+				if (1..=4).contains(&tag.len()) && tag.chars().all(|c| c.is_ascii_digit()) =>
+			{
+				// `#0` is how unique usernames show up in places that still expect a tag
+				(name, tag.parse::<u16>().ok().filter(|&tag| tag != 0))
+			},
+			| _ => (linked, None),
+		};
+
+		let tag_matches = match (self.discriminator, discriminator) {
+			// accounts that still have a tag share their name with others, the tag has to match
+			| (Some(own), linked) => linked == Some(own),
+			| (None, _) => true,
+		};
+
+		(name.eq_ignore_ascii_case(&self.username) && tag_matches)
+			|| self
+				.global_name
+				.as_ref()
+				.is_some_and(|global_name| linked.eq_ignore_ascii_case(global_name))
+	}
 }
 
-pub async fn get_hypixel_linked_socials(uuid: String) -> Result<Links, Error> {
-	let api_key = std::env::var("API_KEY")?;
-	let url = format!(
-		"https://api.hypixel.net/v2/player?key={}&uuid={}",
-		api_key, uuid
-	);
-
-	let response = reqwest::get(&url).await?;
-	let player_data: PlayerResponse = response.json().await?;
-
-	if !player_data.success {
-		if let Some(e) = &player_data.cause {
-			return Err(Box::new(ApiError::Api(e.clone())));
+impl From<&User> for DiscordIdentity {
+	fn from(user: &User) -> Self {
+		DiscordIdentity {
+			username:      user.name.clone(),
+			discriminator: user.discriminator.map(|discriminator| discriminator.get()),
+			global_name:   user.global_name.clone(),
 		}
 	}
+}
 
-	if !player_data.success {
-		return Err(Box::new(std::io::Error::new(
-			std::io::ErrorKind::Other,
-			player_data
-				.cause
-				.unwrap_or_else(|| "Unknown error".to_string()),
-		)));
+impl fmt::Display for DiscordIdentity {
+	fn fmt(
+		&self,
+		f: &mut fmt::Formatter<'_>,
+	) -> fmt::Result {
+		match self.discriminator {
+			| Some(discriminator) => write!(f, "{}#{:04}", self.username, discriminator),
+			| None => write!(f, "{}", self.username),
+		}
 	}
+}
 
-	match player_data.player {
-		| Some(player) => match player.socialMedia {
-			| Some(social_media) => Ok(social_media.links),
-			| None => Ok(Links {
-				TWITTER:   None,
-				YOUTUBE:   None,
-				INSTAGRAM: None,
-				TWITCH:    None,
-				DISCORD:   None,
-				FORUMS:    None,
-			}),
-		},
-		| None => Ok(Links {
-			TWITTER:   None,
-			YOUTUBE:   None,
-			INSTAGRAM: None,
-			TWITCH:    None,
-			DISCORD:   None,
-			FORUMS:    None,
-		}),
-	}
+/// Why a minecraft account's hypixel socials don't point at a discord user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocialMismatch {
+	NotLinked,
+	Different {
+		hypixel: String,
+		discord: String,
+	},
 }
 
-pub async fn is_hypixel_linked_account(
-	uuid: String,
-	discord_user: String,
-) -> Result<bool, Error> {
-	let linked_socials = get_hypixel_linked_socials(uuid).await?;
+impl fmt::Display for SocialMismatch {
+	fn fmt(
+		&self,
+		f: &mut fmt::Formatter<'_>,
+	) -> fmt::Result {
+		match self {
+			| SocialMismatch::NotLinked => write!(f, "Please link your discord on Hypixel"),
+			| SocialMismatch::Different { hypixel, discord } => write!(
+				f,
+				"Hypixel has `{}` linked, but the discord account is `{}`",
+				hypixel.replace("`", "\\`"),
+				discord.replace("`", "\\`")
+			),
+		}
+	}
+}
 
-	if linked_socials.DISCORD.is_none() {
-		return Err(Box::new(std::io::Error::new(
-			std::io::ErrorKind::NotFound,
-			"Please link your discord on Hypixel".to_string(),
-		)));
+/// Compare the discord set in hypixel's socials against a discord user
+pub fn check_discord_link(
+	linked: Option<&str>,
+	discord_user: &DiscordIdentity,
+) -> Result<(), SocialMismatch> {
+	match linked {
+		| None => Err(SocialMismatch::NotLinked),
+		| Some(linked) if discord_user.matches(linked) => Ok(()),
+		| Some(linked) => Err(SocialMismatch::Different {
+			hypixel: linked.to_string(),
+			discord: discord_user.to_string(),
+		}),
 	}
+}
 
-	Ok(linked_socials
-		.DISCORD
-		.map_or(false, |discord| discord == discord_user))
+/// Whether hypixel links `uuid` to `discord_user`. The outer error is for failed requests, the
+/// inner one says why the account isn't linked
+pub async fn check_hypixel_linked_account(
+	hypixel: &HypixelClient,
+	uuid: &str,
+	discord_user: &DiscordIdentity,
+	priority: Priority,
+) -> Result<Result<(), SocialMismatch>, Error> {
+	let linked_socials = get_hypixel_linked_socials(hypixel, uuid, priority).await?;
+
+	Ok(check_discord_link(
+		linked_socials.DISCORD.as_deref(),
+		discord_user,
+	))
 }
 
 pub fn create_error_embed(description: &str) -> CreateEmbed {
@@ -255,3 +371,192 @@ pub fn create_error_embed(description: &str) -> CreateEmbed {
 		.description(description)
 		.color(*ERROR_COLOR.get().expect("ERROR_COLOR is uninitialized"))
 }
+
+/// Send `pages` as a single embed with buttons to flip between them
+pub async fn paginate_embeds(
+	ctx: Context<'_>,
+	title: &str,
+	pages: &[String],
+	color: u32,
+) -> Result<(), Error> {
+	let ctx_id = ctx.id();
+	let prev_button_id = format!("{}prev", ctx_id);
+	let next_button_id = format!("{}next", ctx_id);
+
+	let create_page = |page: usize| {
+		CreateEmbed::default()
+			.title(title)
+			.description(&pages[page])
+			.color(color)
+			.footer(CreateEmbedFooter::new(format!(
+				"Page {}/{}",
+				page + 1,
+				pages.len()
+			)))
+	};
+
+	let mut reply = CreateReply::default().embed(create_page(0));
+	if pages.len() > 1 {
+		reply = reply.components(vec![CreateActionRow::Buttons(vec![
+			CreateButton::new(&prev_button_id).emoji('◀'),
+			CreateButton::new(&next_button_id).emoji('▶'),
+		])]);
+	}
+	ctx.send(reply).await?;
+
+	if pages.len() <= 1 {
+		return Ok(());
+	}
+
+	let mut current_page = 0;
+	while let Some(press) = ComponentInteractionCollector::new(ctx)
+		.filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
+		.timeout(Duration::from_secs(600))
+		.await
+	{
+		if press.data.custom_id == next_button_id {
+			current_page = (current_page + 1) % pages.len();
+		} else if press.data.custom_id == prev_button_id {
+			current_page = current_page.checked_sub(1).unwrap_or(pages.len() - 1);
+		} else {
+			continue;
+		}
+
+		press
+			.create_response(
+				ctx.serenity_context(),
+				CreateInteractionResponse::UpdateMessage(
+					CreateInteractionResponseMessage::new().embed(create_page(current_page)),
+				),
+			)
+			.await?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+	use wiremock::matchers::{path, query_param};
+	use wiremock::{Mock, MockServer, ResponseTemplate};
+
+	use super::*;
+
+	async fn mock_player(discord: Option<&str>) -> MockServer {
+		let server = MockServer::start().await;
+		let player = match discord {
+			| Some(discord) => json!({ "socialMedia": { "links": { "DISCORD": discord } } }),
+			| None => json!({}),
+		};
+		Mock::given(path("/v2/player"))
+			.and(query_param("uuid", "player1"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+				"success": true,
+				"player": player
+			})))
+			.mount(&server)
+			.await;
+		server
+	}
+
+	fn identity(
+		username: &str,
+		discriminator: Option<u16>,
+		global_name: Option<&str>,
+	) -> DiscordIdentity {
+		DiscordIdentity {
+			username: username.to_string(),
+			discriminator,
+			global_name: global_name.map(ToOwned::to_owned),
+		}
+	}
+
+	#[tokio::test]
+	async fn matching_discord_is_linked() {
+		let server = mock_player(Some("cow")).await;
+		let hypixel = HypixelClient::new("test-key", &server.uri(), 300);
+
+		let result = check_hypixel_linked_account(
+			&hypixel,
+			"player1",
+			&DiscordIdentity::from_username("cow"),
+			Priority::Interactive,
+		)
+		.await
+		.unwrap();
+		assert_eq!(result, Ok(()));
+	}
+
+	#[tokio::test]
+	async fn other_discord_is_not_linked() {
+		let server = mock_player(Some("not_cow")).await;
+		let hypixel = HypixelClient::new("test-key", &server.uri(), 300);
+
+		let result = check_hypixel_linked_account(
+			&hypixel,
+			"player1",
+			&DiscordIdentity::from_username("cow"),
+			Priority::Interactive,
+		)
+		.await
+		.unwrap();
+		assert_eq!(
+			result,
+			Err(SocialMismatch::Different {
+				hypixel: "not_cow".to_string(),
+				discord: "cow".to_string(),
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn missing_discord_is_not_linked() {
+		let server = mock_player(None).await;
+		let hypixel = HypixelClient::new("test-key", &server.uri(), 300);
+
+		let result = check_hypixel_linked_account(
+			&hypixel,
+			"player1",
+			&DiscordIdentity::from_username("cow"),
+			Priority::Interactive,
+		)
+		.await
+		.unwrap();
+		assert_eq!(result, Err(SocialMismatch::NotLinked));
+	}
+
+	#[test]
+	fn matches_usernames_and_display_names() {
+		let user = identity("not_a_cow", None, Some("Cow"));
+
+		assert!(user.matches("Not_A_Cow"));
+		assert!(user.matches(" not_a_cow "));
+		assert!(user.matches("cow"));
+		assert!(!user.matches("dog"));
+		assert_eq!(
+			check_discord_link(Some("dog"), &user),
+			Err(SocialMismatch::Different {
+				hypixel: "dog".to_string(),
+				discord: "not_a_cow".to_string(),
+			})
+		);
+	}
+
+	#[test]
+	fn matches_legacy_tags() {
+		let legacy = identity("Cow", Some(1234), None);
+		assert!(legacy.matches("cow#1234"));
+		assert!(!legacy.matches("Cow#4321"));
+		// without the tag the name could belong to anyone
+		assert!(!legacy.matches("Cow"));
+		assert_eq!(legacy.to_string(), "Cow#1234");
+
+		// moved to a unique username, hypixel still has the tag they linked with
+		let migrated = identity("cow", None, None);
+		assert!(migrated.matches("cow#0"));
+		assert!(migrated.matches("Cow#1234"));
+		assert!(!migrated.matches("dog#1234"));
+		assert!(!migrated.matches("cow#12345"));
+	}
+}
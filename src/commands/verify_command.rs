@@ -0,0 +1,193 @@
+use futures::stream::StreamExt;
+use poise::CreateReply;
+use serenity::all::{CreateEmbed, Role};
+
+use crate::commands::utils::{create_error_embed, get_color};
+use crate::commands::verify_utils::{
+	format_nickname,
+	sync_member,
+	top_role_position,
+	verified_role_problem,
+};
+use crate::data::database::{
+	VerificationSettings,
+	get_primary_accounts,
+	get_verification_settings,
+	remove_verification_settings,
+	set_verification_settings,
+};
+use crate::{Context, Error};
+
+#[poise::command(
+	slash_command,
+	prefix_command,
+	subcommands("setup", "disable", "sync"),
+	subcommand_required,
+	guild_only,
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn verify(_ctx: Context<'_>) -> Result<(), Error> { Ok(()) }
+
+/// Give members a role, and optionally a nickname, when they link their account
+#[poise::command(
+	slash_command,
+	prefix_command,
+	guild_only,
+	required_permissions = "MANAGE_GUILD | MANAGE_ROLES"
+)]
+async fn setup(
+	ctx: Context<'_>,
+	#[description = "Role given to linked members"] role: Role,
+	#[description = "Nickname with {ign} and {name}, eg [{ign}] {name}"] nickname: Option<String>,
+) -> Result<(), Error> {
+	let nickname = nickname
+		.map(|nickname| nickname.trim().to_string())
+		.filter(|nickname| !nickname.is_empty());
+	if nickname
+		.as_ref()
+		.is_some_and(|nickname| !nickname.contains("{ign}"))
+	{
+		let embed = create_error_embed("The nickname has to contain `{ign}`");
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let guild_id = ctx.guild_id().unwrap();
+	let guild = guild_id.to_partial_guild(ctx).await?;
+	let invoker_top = if guild.owner_id == ctx.author().id {
+		None
+	} else {
+		let invoker = guild_id.member(ctx, ctx.author().id).await?;
+		Some(top_role_position(&invoker, &guild.roles))
+	};
+	let bot = guild_id.member(ctx, ctx.framework().bot_id).await?;
+	let bot_top = top_role_position(&bot, &guild.roles);
+
+	if let Some(problem) = verified_role_problem(guild_id, &role, invoker_top, bot_top) {
+		let embed = create_error_embed(problem);
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let settings = VerificationSettings {
+		discord_guild_id: guild_id.get(),
+		role_id:          role.id.get(),
+		nickname_format:  nickname,
+	};
+	set_verification_settings(&settings).await?;
+
+	let mut description = format!("Members will get <@&{}> when they link an account", role.id);
+	if let Some(format) = &settings.nickname_format {
+		description.push_str(&format!(
+			"\nTheir nickname will look like `{}`",
+			format_nickname(format, "Not_a_cow", &ctx.author().name)
+		));
+	}
+	description.push_str("\nUse `/verify sync` to update existing members");
+
+	let embed = CreateEmbed::default()
+		.title("Verification Updated")
+		.description(description)
+		.color(get_color(ctx.author().id.get()).await);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+/// Stop giving linked members a role and nickname
+#[poise::command(
+	slash_command,
+	prefix_command,
+	guild_only,
+	required_permissions = "MANAGE_GUILD"
+)]
+async fn disable(ctx: Context<'_>) -> Result<(), Error> {
+	if !remove_verification_settings(ctx.guild_id().unwrap().get()).await? {
+		let embed = create_error_embed("This server has no verified role set");
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	}
+
+	let embed = CreateEmbed::default()
+		.title("Verification Disabled")
+		.description("Roles and nicknames that were already given are kept")
+		.color(get_color(ctx.author().id.get()).await);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
+
+/// Give or take the verified role and nickname of every member based on their linked account
+#[poise::command(
+	slash_command,
+	prefix_command,
+	guild_only,
+	required_permissions = "MANAGE_GUILD"
+)]
+async fn sync(ctx: Context<'_>) -> Result<(), Error> {
+	ctx.defer().await?;
+
+	let guild_id = ctx.guild_id().unwrap();
+	let Some(settings) = get_verification_settings(guild_id.get()).await? else {
+		let embed = create_error_embed(
+			"This server has no verified role set, set one with `/verify setup`",
+		);
+		ctx.send(CreateReply::default().embed(embed)).await?;
+		return Ok(());
+	};
+	let accounts = get_primary_accounts().await?;
+
+	let (mut total, mut failed) = (0u32, 0u32);
+	let (mut added, mut removed, mut renamed) = (0u32, 0u32, 0u32);
+
+	let mut members = guild_id.members_iter(ctx.http()).boxed();
+	while let Some(member) = members.next().await {
+		let member = match member {
+			| Ok(member) => member,
+			| Err(e) => {
+				println!("\x1b[31;1m[ERROR] Failed to list members:\x1b[0m {}", e);
+				let embed = create_error_embed(
+					"Could not list this server's members, the bot needs the Server Members intent",
+				);
+				ctx.send(CreateReply::default().embed(embed)).await?;
+				return Ok(());
+			},
+		};
+		if member.user.bot {
+			continue;
+		}
+		total += 1;
+
+		let primary = accounts.get(&member.user.id.get());
+		match sync_member(ctx.http(), &settings, &member, primary).await {
+			| Ok(outcome) => {
+				added += outcome.role_added as u32;
+				removed += outcome.role_removed as u32;
+				renamed += outcome.renamed as u32;
+			},
+			| Err(e) => {
+				failed += 1;
+				println!(
+					"\x1b[31;1m[ERROR] Failed to sync verification of {}:\x1b[0m {}",
+					member.user.id, e
+				);
+			},
+		}
+	}
+
+	let mut description = format!(
+		"Checked {} members\nGave the role to {}, removed it from {}\nUpdated {} nicknames",
+		total, added, removed, renamed
+	);
+	if failed > 0 {
+		description.push_str(&format!(
+			"\n{} members could not be updated, make sure my role is above <@&{}> and I can manage nicknames",
+			failed, settings.role_id
+		));
+	}
+
+	let embed = CreateEmbed::default()
+		.title("Verification Synced")
+		.description(description)
+		.color(get_color(ctx.author().id.get()).await);
+	ctx.send(CreateReply::default().embed(embed)).await?;
+	Ok(())
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+
+use serenity::all::{EditMember, GuildId, Http, Member, Role, RoleId, StatusCode, UserId};
+
+use crate::Context;
+use crate::data::database::{
+	LinkedAccount,
+	VerificationSettings,
+	get_all_verification_settings,
+	get_linked_accounts,
+};
+
+/// discord's limit on nickname length
+const NICKNAME_LIMIT: usize = 32;
+/// minecraft's limit on ign length
+const IGN_LIMIT: usize = 16;
+
+/// What `sync_member` changed
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SyncOutcome {
+	pub role_added:   bool,
+	pub role_removed: bool,
+	pub renamed:      bool,
+}
+
+/// Fill in `{ign}` and `{name}`, cut to the nickname length limit
+pub fn format_nickname(
+	format: &str,
+	ign: &str,
+	name: &str,
+) -> String {
+	format
+		.replace("{ign}", ign)
+		.replace("{name}", name)
+		.chars()
+		.take(NICKNAME_LIMIT)
+		.collect()
+}
+
+/// Position of the highest role a member has, 0 if they only have `@everyone`
+pub fn top_role_position(
+	member: &Member,
+	roles: &HashMap<RoleId, Role>,
+) -> u16 {
+	member
+		.roles
+		.iter()
+		.filter_map(|role_id| roles.get(role_id))
+		.map(|role| role.position)
+		.max()
+		.unwrap_or(0)
+}
+
+/// Why `role` can't be used as the verified role, if it can't. `invoker_top` is `None` for the
+/// server owner, who can hand out any role
+pub fn verified_role_problem(
+	guild_id: GuildId,
+	role: &Role,
+	invoker_top: Option<u16>,
+	bot_top: u16,
+) -> Option<&'static str> {
+	if role.id.get() == guild_id.get() {
+		Some("`@everyone` can't be the verified role")
+	} else if role.managed {
+		Some("That role is managed by an integration and can't be given out")
+	} else if invoker_top.is_some_and(|top| role.position >= top) {
+		Some("You can only pick a role below your highest role")
+	} else if role.position >= bot_top {
+		Some("My highest role has to be above the verified role")
+	} else {
+		None
+	}
+}
+
+/// Whether `nickname` could have been made from `format`, so only nicknames the bot set are reset
+pub fn is_formatted_nickname(
+	format: &str,
+	nickname: &str,
+	name: &str,
+) -> bool {
+	let format = format.replace("{name}", name);
+	let Some((prefix, suffix)) = format.split_once("{ign}") else {
+		return nickname == format_nickname(&format, "", "");
+	};
+	if nickname.len() >= prefix.len() + suffix.len()
+		&& nickname.starts_with(prefix)
+		&& nickname.ends_with(suffix)
+	{
+		return true;
+	}
+
+	// a nickname at the limit may have been cut short, anywhere after the prefix
+	if nickname.chars().count() < NICKNAME_LIMIT {
+		return false;
+	}
+	let prefix = format_nickname(prefix, "", "");
+	let Some(rest) = nickname.strip_prefix(prefix.as_str()) else {
+		return false;
+	};
+	rest.is_empty()
+		|| rest
+			.char_indices()
+			.skip(1)
+			.take(IGN_LIMIT)
+			.map(|(index, _)| &rest[index..])
+			.chain((rest.chars().count() <= IGN_LIMIT).then_some(""))
+			.any(|cut_suffix| suffix.starts_with(cut_suffix))
+}
+
+/// Give `member` the verified role and nickname if they have a primary account, take them away
+/// if they don't
+pub async fn sync_member(
+	http: &Http,
+	settings: &VerificationSettings,
+	member: &Member,
+	primary: Option<&LinkedAccount>,
+) -> Result<SyncOutcome, serenity::Error> {
+	let role = RoleId::new(settings.role_id);
+	let has_role = member.roles.contains(&role);
+	let name = member
+		.user
+		.global_name
+		.as_deref()
+		.unwrap_or(&member.user.name);
+	let mut outcome = SyncOutcome::default();
+
+	match primary {
+		| Some(account) => {
+			if !has_role {
+				member.add_role(http, role).await?;
+				outcome.role_added = true;
+			}
+			if let Some(format) = &settings.nickname_format {
+				let nickname = format_nickname(format, &account.mc_username, name);
+				if member.nick.as_deref() != Some(nickname.as_str()) {
+					member
+						.guild_id
+						.edit_member(http, member.user.id, EditMember::new().nickname(nickname))
+						.await?;
+					outcome.renamed = true;
+				}
+			}
+		},
+		| None => {
+			if has_role {
+				member.remove_role(http, role).await?;
+				outcome.role_removed = true;
+			}
+			let reset_nickname = match (&settings.nickname_format, &member.nick) {
+				| (Some(format), Some(nickname)) => is_formatted_nickname(format, nickname, name),
+				| _ => false,
+			};
+			if reset_nickname {
+				member
+					.guild_id
+					.edit_member(http, member.user.id, EditMember::new().nickname(""))
+					.await?;
+				outcome.renamed = true;
+			}
+		},
+	}
+
+	Ok(outcome)
+}
+
+/// Update someone's verified role and nickname in every server with a verified role set, after
+/// their linked accounts changed. Failures are only logged, the link itself already succeeded
+pub async fn update_verification(
+	ctx: Context<'_>,
+	user_id: UserId,
+) {
+	let settings = match get_all_verification_settings().await {
+		| Ok(settings) => settings,
+		| Err(e) => {
+			println!(
+				"\x1b[31;1m[ERROR] Failed to load verification settings:\x1b[0m {}",
+				e
+			);
+			return;
+		},
+	};
+	if settings.is_empty() {
+		return;
+	}
+
+	let primary = match get_linked_accounts(user_id.get()).await {
+		| Ok(accounts) => accounts.into_iter().find(|account| account.primary),
+		| Err(e) => {
+			println!(
+				"\x1b[31;1m[ERROR] Failed to load linked accounts of {}:\x1b[0m {}",
+				user_id, e
+			);
+			return;
+		},
+	};
+
+	for settings in &settings {
+		let guild_id = GuildId::new(settings.discord_guild_id);
+		let member = match guild_id.member(ctx, user_id).await {
+			| Ok(member) => member,
+			// not in that server, or the bot was removed from it
+			| Err(serenity::Error::Http(e))
+				if matches!(
+					e.status_code(),
+					Some(StatusCode::NOT_FOUND | StatusCode::FORBIDDEN)
+				) =>
+			{
+				continue;
+			},
+			| Err(e) => {
+				println!(
+					"\x1b[31;1m[ERROR] Failed to fetch {} in {}:\x1b[0m {}",
+					user_id, guild_id, e
+				);
+				continue;
+			},
+		};
+
+		if let Err(e) = sync_member(ctx.http(), settings, &member, primary.as_ref()).await {
+			println!(
+				"\x1b[31;1m[ERROR] Failed to update verification of {} in {}:\x1b[0m {}",
+				user_id, guild_id, e
+			);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn formats_and_recognises_nicknames() {
+		let format = "[{ign}] {name}";
+		let nickname = format_nickname(format, "Not_a_cow", "cow");
+		assert_eq!(nickname, "[Not_a_cow] cow");
+		assert!(is_formatted_nickname(format, &nickname, "cow"));
+		assert!(!is_formatted_nickname(format, "cow", "cow"));
+		assert!(!is_formatted_nickname(format, "[Not_a_cow] dog", "cow"));
+
+		let long = format_nickname(format, "Not_a_cow", &"a".repeat(40));
+		assert_eq!(long.chars().count(), NICKNAME_LIMIT);
+	}
+
+	#[test]
+	fn recognises_nicknames_cut_to_the_limit() {
+		let name = "a_really_long_display_name";
+		for format in ["[{ign}] {name}", "{name} | {ign}", "{ign} - {name}"] {
+			let nickname = format_nickname(format, "Not_a_cow_at_all", name);
+			assert_eq!(nickname.chars().count(), NICKNAME_LIMIT);
+			assert!(
+				is_formatted_nickname(format, &nickname, name),
+				"{}",
+				nickname
+			);
+		}
+
+		let staff_set = "b".repeat(NICKNAME_LIMIT);
+		assert!(!is_formatted_nickname("[{ign}] {name}", &staff_set, name));
+		assert!(!is_formatted_nickname(
+			"{ign} the cow",
+			&format!("{}x", "a".repeat(NICKNAME_LIMIT - 1)),
+			name
+		));
+	}
+
+	fn role(
+		id: u64,
+		position: u16,
+		managed: bool,
+	) -> Role {
+		serde_json::from_value(serde_json::json!({
+			"id": id.to_string(),
+			"name": "Verified",
+			"color": 0,
+			"hoist": false,
+			"managed": managed,
+			"position": position,
+			"permissions": "0",
+		}))
+		.unwrap()
+	}
+
+	#[test]
+	fn rejects_roles_that_cant_be_given_out() {
+		let guild_id = GuildId::new(1);
+
+		assert!(verified_role_problem(guild_id, &role(2, 3, false), Some(5), 5).is_none());
+		assert!(verified_role_problem(guild_id, &role(1, 0, false), Some(5), 5).is_some());
+		assert!(verified_role_problem(guild_id, &role(2, 3, true), Some(5), 5).is_some());
+		assert!(verified_role_problem(guild_id, &role(2, 5, false), Some(5), 9).is_some());
+		assert!(verified_role_problem(guild_id, &role(2, 5, false), None, 5).is_some());
+		// the owner can pick any role below the bot's
+		assert!(verified_role_problem(guild_id, &role(2, 8, false), None, 9).is_none());
+	}
+}
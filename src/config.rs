@@ -0,0 +1,396 @@
+use std::env::var;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use mongodb::{Client, Database};
+use serde::Deserialize;
+
+use crate::CONFIG;
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Settings read from `config.toml`, overridden by env vars and `.env`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub api_key: String,
+	pub bot_token: String,
+	pub mongo_url: String,
+	pub hypixel_api_url: String,
+	pub elite_api_url: String,
+	/// requests per 5 minutes allowed by the hypixel api key
+	pub key_limit: u32,
+	/// directory the sqlite databases are stored in
+	pub data_dir: PathBuf,
+	/// defaults to `users.db` in `data_dir`
+	pub users_db: Option<PathBuf>,
+	/// defaults to `tags.db` in `data_dir`
+	pub tags_db: Option<PathBuf>,
+	pub mongo_database: String,
+	pub uptime_collection: String,
+	pub gains_collection: String,
+	pub tracked_players_collection: String,
+	pub tracked_guilds_collection: String,
+	pub prefix: String,
+	pub error_color: u32,
+	/// minutes between uptime updates
+	pub update_interval: u64,
+	/// hours between collection and skill gain updates
+	pub gains_interval: u64,
+	/// hours between gexp requirement reports
+	pub requirement_interval: u64,
+	/// hours before a linked account's ign and hypixel socials are checked again
+	pub link_refresh_interval: u64,
+	/// channel background task crashes are reported to
+	pub log_channel: Option<u64>,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			api_key: String::new(),
+			bot_token: String::new(),
+			mongo_url: String::new(),
+			hypixel_api_url: crate::hypixel::client::DEFAULT_BASE_URL.to_string(),
+			elite_api_url: crate::elite::client::DEFAULT_BASE_URL.to_string(),
+			key_limit: 300,
+			data_dir: PathBuf::from("src/data"),
+			users_db: None,
+			tags_db: None,
+			mongo_database: "Players".to_string(),
+			uptime_collection: "Uptime".to_string(),
+			gains_collection: "Gains".to_string(),
+			tracked_players_collection: "TrackedPlayers".to_string(),
+			tracked_guilds_collection: "TrackedGuilds".to_string(),
+			prefix: "cow ".to_string(),
+			error_color: 0x770505,
+			update_interval: 10,
+			gains_interval: 6,
+			requirement_interval: 168,
+			link_refresh_interval: 24,
+			log_channel: None,
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+	Read(PathBuf, std::io::Error),
+	Parse(PathBuf, toml::de::Error),
+	Invalid(Vec<String>),
+}
+
+impl std::error::Error for ConfigError {}
+
+impl fmt::Display for ConfigError {
+	fn fmt(
+		&self,
+		f: &mut fmt::Formatter<'_>,
+	) -> fmt::Result {
+		match self {
+			| ConfigError::Read(path, e) => write!(f, "Could not read {}: {}", path.display(), e),
+			| ConfigError::Parse(path, e) => write!(f, "Could not parse {}: {}", path.display(), e),
+			| ConfigError::Invalid(problems) => write!(f, "{}", problems.join("\n")),
+		}
+	}
+}
+
+impl Config {
+	/// Load `CONFIG_FILE` (default `config.toml`, optional) and apply env overrides
+	pub fn load() -> Result<Config, ConfigError> {
+		let (path, required) = match var("CONFIG_FILE") {
+			| Ok(path) => (PathBuf::from(path), true),
+			| Err(_) => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+		};
+
+		let config = if required || path.exists() {
+			let contents =
+				std::fs::read_to_string(&path).map_err(|e| ConfigError::Read(path.clone(), e))?;
+			Config::from_toml(&contents).map_err(|e| ConfigError::Parse(path, e))?
+		} else {
+			Config::default()
+		};
+
+		config.with_env(|name| var(name).ok())
+	}
+
+	pub fn from_toml(contents: &str) -> Result<Config, toml::de::Error> { toml::from_str(contents) }
+
+	/// Override fields with env vars named after them in upper case, then validate
+	pub fn with_env(
+		mut self,
+		env: impl Fn(&str) -> Option<String>,
+	) -> Result<Config, ConfigError> {
+		let mut vars = EnvOverrides {
+			env:      &env,
+			problems: Vec::new(),
+		};
+
+		vars.set("API_KEY", &mut self.api_key, "text");
+		vars.set("BOT_TOKEN", &mut self.bot_token, "text");
+		vars.set("MONGO_URL", &mut self.mongo_url, "text");
+		vars.set("HYPIXEL_API_URL", &mut self.hypixel_api_url, "a url");
+		vars.set("ELITE_API_URL", &mut self.elite_api_url, "a url");
+		vars.set("KEY_LIMIT", &mut self.key_limit, "a whole number");
+		vars.set("DATA_DIR", &mut self.data_dir, "a path");
+		vars.set_some("USERS_DB", &mut self.users_db, "a path");
+		vars.set_some("TAGS_DB", &mut self.tags_db, "a path");
+		vars.set("MONGO_DATABASE", &mut self.mongo_database, "text");
+		vars.set("UPTIME_COLLECTION", &mut self.uptime_collection, "text");
+		vars.set("GAINS_COLLECTION", &mut self.gains_collection, "text");
+		vars.set(
+			"TRACKED_PLAYERS_COLLECTION",
+			&mut self.tracked_players_collection,
+			"text",
+		);
+		vars.set(
+			"TRACKED_GUILDS_COLLECTION",
+			&mut self.tracked_guilds_collection,
+			"text",
+		);
+		vars.set(
+			"UPDATE_INTERVAL",
+			&mut self.update_interval,
+			"a whole number of minutes",
+		);
+		vars.set(
+			"GAINS_INTERVAL",
+			&mut self.gains_interval,
+			"a whole number of hours",
+		);
+		vars.set(
+			"REQUIREMENT_INTERVAL",
+			&mut self.requirement_interval,
+			"a whole number of hours",
+		);
+		vars.set(
+			"LINK_REFRESH_INTERVAL",
+			&mut self.link_refresh_interval,
+			"a whole number of hours",
+		);
+		vars.set_some("LOG_CHANNEL", &mut self.log_channel, "a channel id");
+
+		// not trimmed, the space in `cow ` is part of the prefix
+		if let Some(prefix) = env("PREFIX").filter(|prefix| !prefix.is_empty()) {
+			self.prefix = prefix;
+		}
+		if let Some(color) = env("ERROR_COLOR") {
+			match u32::from_str_radix(
+				color
+					.trim()
+					.trim_start_matches("0x")
+					.trim_start_matches('#'),
+				16,
+			) {
+				| Ok(color) => self.error_color = color,
+				| Err(_) => vars.problems.push(format!(
+					"`ERROR_COLOR` must be a hex color, got `{}`",
+					color
+				)),
+			}
+		}
+
+		let mut problems = vars.problems;
+		problems.extend(self.problems());
+		if problems.is_empty() {
+			Ok(self)
+		} else {
+			Err(ConfigError::Invalid(problems))
+		}
+	}
+
+	fn problems(&self) -> Vec<String> {
+		let mut problems = Vec::new();
+
+		for (name, value) in [
+			("API_KEY", &self.api_key),
+			("BOT_TOKEN", &self.bot_token),
+			("MONGO_URL", &self.mongo_url),
+		] {
+			if value.is_empty() {
+				problems.push(format!(
+					"Missing `{}`, please include this in your .env file",
+					name
+				));
+			}
+		}
+
+		for (name, value) in [
+			("KEY_LIMIT", self.key_limit as u64),
+			("UPDATE_INTERVAL", self.update_interval),
+			("GAINS_INTERVAL", self.gains_interval),
+			("REQUIREMENT_INTERVAL", self.requirement_interval),
+			("LINK_REFRESH_INTERVAL", self.link_refresh_interval),
+		] {
+			if value == 0 {
+				problems.push(format!("`{}` must be at least 1", name));
+			}
+		}
+
+		if self.prefix.trim().is_empty() {
+			problems.push("`PREFIX` can't be empty".to_string());
+		}
+		if self.error_color > 0xffffff {
+			problems.push("`ERROR_COLOR` must be a 6 character hex color".to_string());
+		}
+
+		problems
+	}
+
+	pub fn users_db(&self) -> PathBuf {
+		self.users_db
+			.clone()
+			.unwrap_or_else(|| self.data_dir.join("users.db"))
+	}
+
+	pub fn tags_db(&self) -> PathBuf {
+		self.tags_db
+			.clone()
+			.unwrap_or_else(|| self.data_dir.join("tags.db"))
+	}
+
+	/// Directories the databases live in, created at startup
+	pub fn db_dirs(&self) -> Vec<PathBuf> {
+		[self.users_db(), self.tags_db()]
+			.iter()
+			.filter_map(|path| path.parent().map(Path::to_path_buf))
+			.collect()
+	}
+
+	pub fn database(
+		&self,
+		client: &Client,
+	) -> Database {
+		client.database(&self.mongo_database)
+	}
+}
+
+struct EnvOverrides<'a> {
+	env:      &'a dyn Fn(&str) -> Option<String>,
+	problems: Vec<String>,
+}
+
+impl EnvOverrides<'_> {
+	fn get(
+		&self,
+		name: &str,
+	) -> Option<String> {
+		(self.env)(name)
+			.map(|value| value.trim().to_string())
+			.filter(|value| !value.is_empty())
+	}
+
+	/// The parsed value of `name`, if it is set and valid
+	fn parse<T: FromStr>(
+		&mut self,
+		name: &str,
+		expected: &str,
+	) -> Option<T> {
+		let value = self.get(name)?;
+		let parsed = value.parse().ok();
+		if parsed.is_none() {
+			self.problems
+				.push(format!("`{}` must be {}, got `{}`", name, expected, value));
+		}
+		parsed
+	}
+
+	fn set<T: FromStr>(
+		&mut self,
+		name: &str,
+		field: &mut T,
+		expected: &str,
+	) {
+		if let Some(value) = self.parse(name, expected) {
+			*field = value;
+		}
+	}
+
+	fn set_some<T: FromStr>(
+		&mut self,
+		name: &str,
+		field: &mut Option<T>,
+		expected: &str,
+	) {
+		if let Some(value) = self.parse(name, expected) {
+			*field = Some(value);
+		}
+	}
+}
+
+pub fn config() -> &'static Config { CONFIG.get().expect("CONFIG is uninitialized") }
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use super::*;
+
+	fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+		let vars: HashMap<String, String> = vars
+			.iter()
+			.map(|(name, value)| (name.to_string(), value.to_string()))
+			.collect();
+		move |name| vars.get(name).cloned()
+	}
+
+	const REQUIRED: [(&str, &str); 3] = [
+		("API_KEY", "key"),
+		("BOT_TOKEN", "token"),
+		("MONGO_URL", "mongodb://localhost:27017"),
+	];
+
+	#[test]
+	fn env_overrides_the_config_file() {
+		let config = Config::from_toml(
+			"data_dir = \"/data\"\nupdate_interval = 5\nmongo_database = \"Test\"\nerror_color = 0xff0000",
+		)
+		.unwrap();
+		let mut vars = REQUIRED.to_vec();
+		vars.push(("UPDATE_INTERVAL", "15"));
+		vars.push(("ERROR_COLOR", "#00ff00"));
+
+		let config = config.with_env(env(&vars)).unwrap();
+		assert_eq!(config.update_interval, 15);
+		assert_eq!(config.error_color, 0x00ff00);
+		assert_eq!(config.mongo_database, "Test");
+		assert_eq!(config.gains_interval, 6);
+		assert_eq!(config.users_db(), PathBuf::from("/data/users.db"));
+		assert_eq!(config.tags_db(), PathBuf::from("/data/tags.db"));
+	}
+
+	#[test]
+	fn reports_every_problem() {
+		let error = Config::default()
+			.with_env(env(&[("KEY_LIMIT", "lots"), ("GAINS_INTERVAL", "0")]))
+			.unwrap_err();
+		let ConfigError::Invalid(problems) = error else {
+			panic!("expected validation errors, got {}", error);
+		};
+
+		assert_eq!(problems.len(), 5, "{:?}", problems);
+		assert!(
+			problems
+				.iter()
+				.any(|problem| problem.contains("`KEY_LIMIT`"))
+		);
+		assert!(problems.iter().any(|problem| problem.contains("`API_KEY`")));
+		assert!(
+			problems
+				.iter()
+				.any(|problem| problem.contains("`GAINS_INTERVAL`"))
+		);
+	}
+
+	#[test]
+	fn example_config_parses() {
+		let config = Config::from_toml(include_str!("../config.example.toml")).unwrap();
+		assert_eq!(config.error_color, Config::default().error_color);
+	}
+
+	#[test]
+	fn rejects_unknown_keys() {
+		assert!(Config::from_toml("update_intreval = 5").is_err());
+	}
+}
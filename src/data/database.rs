@@ -1,16 +1,1300 @@
-use rusqlite::{Connection, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
 
-pub fn create_users_table() -> Result<()> {
-	let conn = Connection::open("src/data/users.db")?;
+use chrono::Utc;
+use chrono_tz::Tz;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{Connection, OptionalExtension, Result, TransactionBehavior, params};
+use tokio::task;
+
+use crate::{DB_POOL, Error, USERS_DB_POOL};
+
+pub type SqlitePool = Pool<SqliteConnectionManager>;
+
+/// How long a connection waits for another one's write lock before giving up
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pool of connections in WAL mode, so reads don't wait on writes
+pub fn create_pool(path: &Path) -> std::result::Result<SqlitePool, r2d2::Error> {
+	let manager = SqliteConnectionManager::file(path).with_init(|conn| {
+		conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+		conn.busy_timeout(BUSY_TIMEOUT)
+	});
+	Pool::new(manager)
+}
+
+/// Run `query` on a connection from `pool` without blocking the async runtime
+pub async fn with_connection<T, F>(
+	pool: &SqlitePool,
+	query: F,
+) -> Result<T, Error>
+where
+	T: Send + 'static,
+	F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+{
+	let pool = pool.clone();
+	task::spawn_blocking(move || {
+		let mut conn = pool.get()?;
+		Ok(query(&mut conn)?)
+	})
+	.await?
+}
+
+/// Run `query` on a pooled `users.db` connection
+pub async fn with_users_db<T, F>(query: F) -> Result<T, Error>
+where
+	T: Send + 'static,
+	F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+{
+	let pool = USERS_DB_POOL.get().expect("USERS_DB_POOL is uninitialized");
+	with_connection(pool, query).await
+}
+
+/// A schema change, applied once and in order of `version`
+pub struct Migration {
+	pub version:     i64,
+	pub description: &'static str,
+	pub up:          fn(&Connection) -> Result<()>,
+}
+
+/// Migrations for `users.db`, append new ones to the end and never edit applied ones
+pub const USERS_MIGRATIONS: &[Migration] = &[
+	Migration {
+		version:     1,
+		description: "create users and guild_requirements",
+		up:          create_users_tables,
+	},
+	Migration {
+		version:     2,
+		description: "add timezone, uptime window and privacy settings",
+		up:          add_user_settings,
+	},
+	// version 3 only indexed `users.mc_uuid`, which `linked_accounts` replaced. Rows were always
+	// keyed by discord id, so looking them up by id needed no backfill
+	Migration {
+		version:     4,
+		description: "audit admin changes to linked accounts",
+		up:          create_link_audit_table,
+	},
+	Migration {
+		version:     5,
+		description: "allow linking more than one minecraft account",
+		up:          create_linked_accounts_table,
+	},
+	Migration {
+		version:     6,
+		description: "keep linked igns up to date and flag links hypixel no longer confirms",
+		up:          add_linked_account_checks,
+	},
+	Migration {
+		version:     7,
+		description: "give linked members a verified role and nickname",
+		up:          create_verification_settings_table,
+	},
+];
+
+/// Migrations for `tags.db`, append new ones to the end and never edit applied ones
+pub const TAGS_MIGRATIONS: &[Migration] = &[Migration {
+	version:     1,
+	description: "merge the per server tag tables into one table",
+	up:          merge_guild_tag_tables,
+}];
+
+/// Apply every migration newer than the database's schema version, returns the new version
+pub fn run_migrations(
+	conn: &mut Connection,
+	migrations: &[Migration],
+) -> Result<i64> {
 	conn.execute(
+		"CREATE TABLE IF NOT EXISTS schema_version (
+			version INTEGER PRIMARY KEY,
+			description TEXT NOT NULL,
+			applied_at INTEGER NOT NULL
+			)",
+		[],
+	)?;
+	let current: i64 = conn.query_row(
+		"SELECT COALESCE(MAX(version), 0) FROM schema_version",
+		[],
+		|row| row.get(0),
+	)?;
+	let mut version = current;
+
+	for migration in migrations
+		.iter()
+		.filter(|migration| migration.version > current)
+	{
+		// a failed migration is rolled back so it can be retried on the next start
+		let tx = conn.transaction()?;
+		(migration.up)(&tx)?;
+		tx.execute(
+			"INSERT INTO schema_version (version, description, applied_at) VALUES (?1, ?2, ?3)",
+			params![
+				migration.version,
+				migration.description,
+				Utc::now().timestamp()
+			],
+		)?;
+		tx.commit()?;
+
+		println!(
+			"\x1b[34m[INFO] Applied migration {}: {}\x1b[0m",
+			migration.version, migration.description
+		);
+		version = migration.version;
+	}
+
+	Ok(version)
+}
+
+pub async fn migrate_users_db() -> Result<i64, Error> {
+	with_users_db(|conn| run_migrations(conn, USERS_MIGRATIONS)).await
+}
+
+pub async fn migrate_tags_db() -> Result<i64, Error> {
+	let pool = DB_POOL.get().expect("DB_POOL is uninitialized");
+	with_connection(pool, |conn| run_migrations(conn, TAGS_MIGRATIONS)).await
+}
+
+/// The schema from before migrations existed, so existing databases are adopted as they are
+fn create_users_tables(conn: &Connection) -> Result<()> {
+	conn.execute_batch(
 		"CREATE TABLE IF NOT EXISTS users (
 			id INTEGER PRIMARY KEY,
 			username TEXT NOT NULL,
 			mc_username TEXT,
 			mc_uuid TEXT,
 			color TEXT
+			);
+		CREATE TABLE IF NOT EXISTS guild_requirements (
+			discord_guild_id INTEGER PRIMARY KEY,
+			hypixel_guild_id TEXT NOT NULL,
+			hypixel_guild_name TEXT NOT NULL,
+			gexp INTEGER NOT NULL,
+			channel_id INTEGER NOT NULL,
+			last_report INTEGER NOT NULL DEFAULT 0
+			);",
+	)
+}
+
+fn add_user_settings(conn: &Connection) -> Result<()> {
+	// some databases already got these columns before migrations existed
+	add_column_if_missing(conn, "users", "timezone", "TEXT")?;
+	add_column_if_missing(conn, "users", "uptime_window", "INTEGER")?;
+	add_column_if_missing(conn, "users", "private", "INTEGER NOT NULL DEFAULT 0")
+}
+
+fn create_link_audit_table(conn: &Connection) -> Result<()> {
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS link_audit (
+			id INTEGER PRIMARY KEY,
+			action TEXT NOT NULL,
+			user_id INTEGER NOT NULL,
+			actor_id INTEGER NOT NULL,
+			previous_mc_uuid TEXT,
+			mc_username TEXT,
+			mc_uuid TEXT,
+			created_at INTEGER NOT NULL
+			)",
+		[],
+	)?;
+	Ok(())
+}
+
+/// Linked accounts move out of `users`, its `mc_username` and `mc_uuid` columns are left
+/// untouched so an older build can still read them
+fn create_linked_accounts_table(conn: &Connection) -> Result<()> {
+	conn.execute_batch(
+		"CREATE TABLE IF NOT EXISTS linked_accounts (
+			user_id INTEGER NOT NULL,
+			mc_uuid TEXT NOT NULL,
+			mc_username TEXT NOT NULL,
+			is_primary INTEGER NOT NULL DEFAULT 0,
+			verified_at INTEGER,
+			PRIMARY KEY (user_id, mc_uuid)
+			);
+		CREATE INDEX IF NOT EXISTS linked_accounts_mc_uuid ON linked_accounts (mc_uuid);
+		INSERT OR IGNORE INTO linked_accounts (user_id, mc_uuid, mc_username, is_primary)
+			SELECT id, mc_uuid, COALESCE(mc_username, mc_uuid), 1 FROM users
+			WHERE mc_uuid IS NOT NULL;",
+	)
+}
+
+fn add_linked_account_checks(conn: &Connection) -> Result<()> {
+	add_column_if_missing(conn, "linked_accounts", "checked_at", "INTEGER")?;
+	add_column_if_missing(conn, "linked_accounts", "flagged_at", "INTEGER")?;
+	add_column_if_missing(conn, "linked_accounts", "flag_reason", "TEXT")?;
+	conn.execute_batch(
+		"CREATE TABLE IF NOT EXISTS name_history (
+			id INTEGER PRIMARY KEY,
+			mc_uuid TEXT NOT NULL,
+			previous_username TEXT NOT NULL,
+			mc_username TEXT NOT NULL,
+			changed_at INTEGER NOT NULL
+			);
+		CREATE INDEX IF NOT EXISTS name_history_mc_uuid ON name_history (mc_uuid);",
+	)
+}
+
+fn create_verification_settings_table(conn: &Connection) -> Result<()> {
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS verification_settings (
+			discord_guild_id INTEGER PRIMARY KEY,
+			role_id INTEGER NOT NULL,
+			nickname_format TEXT
+			)",
+		[],
+	)?;
+	Ok(())
+}
+
+fn merge_guild_tag_tables(conn: &Connection) -> Result<()> {
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS tags (
+			guild_id INTEGER NOT NULL,
+			name TEXT NOT NULL,
+			content TEXT NOT NULL,
+			PRIMARY KEY (guild_id, name)
 			)",
 		[],
 	)?;
+
+	let tables = conn
+		.prepare(
+			"SELECT name FROM sqlite_master WHERE type = 'table' AND name LIKE 'tags\\_%' ESCAPE '\\'",
+		)?
+		.query_map([], |row| row.get::<_, String>(0))?
+		.collect::<Result<Vec<String>>>()?;
+
+	for table in tables {
+		let Ok(guild_id) = table.trim_start_matches("tags_").parse::<u64>() else {
+			continue;
+		};
+		let copied = conn.execute(
+			&format!(
+				"INSERT OR IGNORE INTO tags (guild_id, name, content) SELECT ?1, name, content FROM {}",
+				table
+			),
+			params![guild_id as i64],
+		)?;
+		let expected: usize =
+			conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+				row.get(0)
+			})?;
+		// fails the migration, and rolls it back, instead of losing tags that clashed
+		if copied != expected {
+			return Err(rusqlite::Error::SqliteFailure(
+				rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CONSTRAINT),
+				Some(format!(
+					"only {} of {} tags in {} could be merged",
+					copied, expected, table
+				)),
+			));
+		}
+
+		// kept for a release in case anything went missing, a later migration drops them
+		conn.execute(
+			&format!("ALTER TABLE {} RENAME TO legacy_{}", table, table),
+			[],
+		)?;
+	}
+	Ok(())
+}
+
+fn add_column_if_missing(
+	conn: &Connection,
+	table: &str,
+	column: &str,
+	definition: &str,
+) -> Result<()> {
+	let exists = conn
+		.prepare(&format!(
+			"SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1",
+			table
+		))?
+		.exists(params![column])?;
+	if !exists {
+		conn.execute(
+			&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+			[],
+		)?;
+	}
+	Ok(())
+}
+
+/// Everything the bot stores about a discord user
+#[derive(Debug, Clone)]
+pub struct UserProfile {
+	pub id: u64,
+	pub username: String,
+	pub color: Option<u32>,
+	pub timezone: Option<Tz>,
+	/// days `/uptime` shows when no window is given
+	pub uptime_window: Option<i64>,
+	/// hide this user's stats from everyone else
+	pub private: bool,
+}
+
+impl UserProfile {
+	pub fn new(
+		id: u64,
+		username: &str,
+	) -> Self {
+		UserProfile {
+			id,
+			username: username.to_string(),
+			color: None,
+			timezone: None,
+			uptime_window: None,
+			private: false,
+		}
+	}
+
+	/// Clear every setting, linked accounts are kept
+	pub fn reset_settings(&mut self) {
+		self.color = None;
+		self.timezone = None;
+		self.uptime_window = None;
+		self.private = false;
+	}
+
+	/// Whether `viewer` should be kept from seeing this user's stats
+	pub fn hides_stats_from(
+		&self,
+		viewer: u64,
+	) -> bool {
+		self.private && self.id != viewer
+	}
+
+	fn from_row(row: &rusqlite::Row) -> Result<Self> {
+		let color: Option<String> = row.get(2)?;
+		let timezone: Option<String> = row.get(3)?;
+
+		Ok(UserProfile {
+			id: row.get::<_, i64>(0)? as u64,
+			username: row.get(1)?,
+			color: color
+				.and_then(|color| u32::from_str_radix(color.trim_start_matches("0x"), 16).ok()),
+			timezone: timezone.and_then(|timezone| timezone.parse().ok()),
+			uptime_window: row.get(4)?,
+			private: row.get(5)?,
+		})
+	}
+}
+
+const USER_PROFILE_COLUMNS: &str = "id, username, color, timezone, uptime_window, private";
+
+pub async fn get_user_profile(user_id: u64) -> Result<Option<UserProfile>, Error> {
+	with_users_db(move |conn| {
+		conn.query_row(
+			&format!("SELECT {} FROM users WHERE id = ?1", USER_PROFILE_COLUMNS),
+			params![user_id as i64],
+			UserProfile::from_row,
+		)
+		.optional()
+	})
+	.await
+}
+
+/// Profile of the discord user a minecraft account is linked to
+pub async fn get_user_profile_by_uuid(mc_uuid: &str) -> Result<Option<UserProfile>, Error> {
+	let mc_uuid = mc_uuid.to_string();
+	with_users_db(move |conn| {
+		conn.query_row(
+			&format!(
+				"SELECT {} FROM users WHERE id = (
+					SELECT user_id FROM linked_accounts WHERE mc_uuid = ?1
+					ORDER BY is_primary DESC LIMIT 1
+				)",
+				USER_PROFILE_COLUMNS
+			),
+			params![mc_uuid],
+			UserProfile::from_row,
+		)
+		.optional()
+	})
+	.await
+}
+
+/// Minecraft uuids linked by users who hide their stats from `viewer`
+pub async fn get_private_uuids(viewer: u64) -> Result<HashSet<String>, Error> {
+	with_users_db(move |conn| {
+		let mut stmt = conn.prepare(
+			"SELECT linked_accounts.mc_uuid FROM linked_accounts
+				JOIN users ON users.id = linked_accounts.user_id
+				WHERE users.private = 1 AND users.id != ?1",
+		)?;
+		let rows = stmt.query_map(params![viewer as i64], |row| row.get(0))?;
+		rows.collect()
+	})
+	.await
+}
+
+/// Keep the stored username in sync with discord, it is only used for display
+pub async fn refresh_username(
+	user_id: u64,
+	username: &str,
+) -> Result<(), Error> {
+	let username = username.to_string();
+	with_users_db(move |conn| {
+		conn.execute(
+			"UPDATE users SET username = ?2 WHERE id = ?1 AND username != ?2",
+			params![user_id as i64, username],
+		)?;
+		Ok(())
+	})
+	.await
+}
+
+fn upsert_user_profile(
+	conn: &Connection,
+	profile: &UserProfile,
+) -> Result<()> {
+	conn.execute(
+		&format!(
+			"INSERT INTO users ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+				ON CONFLICT(id) DO UPDATE SET
+					username = excluded.username,
+					color = excluded.color,
+					timezone = excluded.timezone,
+					uptime_window = excluded.uptime_window,
+					private = excluded.private",
+			USER_PROFILE_COLUMNS
+		),
+		params![
+			profile.id as i64,
+			profile.username,
+			profile.color.map(|color| format!("0x{:06x}", color)),
+			profile.timezone.map(|timezone| timezone.name()),
+			profile.uptime_window,
+			profile.private,
+		],
+	)?;
+	Ok(())
+}
+
+/// Apply `update` to a user's profile, creating it if it doesn't exist. The read and write
+/// share a transaction so settings changed at the same time don't overwrite each other
+pub async fn update_user_profile<F>(
+	user_id: u64,
+	username: &str,
+	update: F,
+) -> Result<UserProfile, Error>
+where
+	F: FnOnce(&mut UserProfile) + Send + 'static,
+{
+	let username = username.to_string();
+	with_users_db(move |conn| update_user_profile_in(conn, user_id, &username, update)).await
+}
+
+fn update_user_profile_in(
+	conn: &mut Connection,
+	user_id: u64,
+	username: &str,
+	update: impl FnOnce(&mut UserProfile),
+) -> Result<UserProfile> {
+	// take the write lock before reading, a deferred transaction could still lose the race
+	let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+	let mut profile = tx
+		.query_row(
+			&format!("SELECT {} FROM users WHERE id = ?1", USER_PROFILE_COLUMNS),
+			params![user_id as i64],
+			UserProfile::from_row,
+		)
+		.optional()?
+		.unwrap_or_else(|| UserProfile::new(user_id, username));
+	profile.username = username.to_string();
+	update(&mut profile);
+	upsert_user_profile(&tx, &profile)?;
+	tx.commit()?;
+	Ok(profile)
+}
+
+/// A minecraft account linked to a discord user
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkedAccount {
+	pub user_id:     u64,
+	pub mc_uuid:     String,
+	pub mc_username: String,
+	/// the account used when no other one is picked
+	pub primary:     bool,
+	/// unix timestamp of when hypixel's socials confirmed the link, `None` if an admin forced it
+	pub verified_at: Option<i64>,
+	/// why the last check found the link is no longer confirmed by hypixel
+	pub flag_reason: Option<String>,
+}
+
+impl LinkedAccount {
+	fn from_row(row: &rusqlite::Row) -> Result<Self> {
+		Ok(LinkedAccount {
+			user_id:     row.get::<_, i64>(0)? as u64,
+			mc_uuid:     row.get(1)?,
+			mc_username: row.get(2)?,
+			primary:     row.get(3)?,
+			verified_at: row.get(4)?,
+			flag_reason: row.get(5)?,
+		})
+	}
+}
+
+/// Pick a linked account by its position in the list, starting at 1, or by ign or uuid
+pub fn select_account<'a>(
+	accounts: &'a [LinkedAccount],
+	selector: &str,
+) -> Option<&'a LinkedAccount> {
+	let selector = selector.trim();
+	if let Ok(position) = selector.parse::<usize>() {
+		return position
+			.checked_sub(1)
+			.and_then(|index| accounts.get(index));
+	}
+
+	let uuid = selector.replace('-', "");
+	accounts.iter().find(|account| {
+		account.mc_username.eq_ignore_ascii_case(selector)
+			|| account.mc_uuid.eq_ignore_ascii_case(&uuid)
+	})
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkAction {
+	Force,
+	Remove,
+}
+
+impl LinkAction {
+	fn as_str(&self) -> &'static str {
+		match self {
+			| LinkAction::Force => "force",
+			| LinkAction::Remove => "remove",
+		}
+	}
+}
+
+/// Who made an admin change to someone else's linked accounts
+#[derive(Debug, Clone, Copy)]
+pub struct LinkAudit {
+	pub action:   LinkAction,
+	pub actor_id: u64,
+}
+
+fn record_link_audit(
+	conn: &Connection,
+	audit: LinkAudit,
+	user_id: u64,
+	previous_mc_uuid: Option<&str>,
+	account: Option<&LinkedAccount>,
+) -> Result<()> {
+	conn.execute(
+		"INSERT INTO link_audit
+			(action, user_id, actor_id, previous_mc_uuid, mc_username, mc_uuid, created_at)
+			VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+		params![
+			audit.action.as_str(),
+			user_id as i64,
+			audit.actor_id as i64,
+			previous_mc_uuid,
+			account.map(|account| &account.mc_username),
+			account.map(|account| &account.mc_uuid),
+			Utc::now().timestamp(),
+		],
+	)?;
 	Ok(())
 }
+
+const LINKED_ACCOUNT_COLUMNS: &str =
+	"user_id, mc_uuid, mc_username, is_primary, verified_at, flag_reason";
+
+fn query_linked_accounts(
+	conn: &Connection,
+	user_id: u64,
+) -> Result<Vec<LinkedAccount>> {
+	let mut stmt = conn.prepare(&format!(
+		"SELECT {} FROM linked_accounts WHERE user_id = ?1 ORDER BY is_primary DESC, rowid",
+		LINKED_ACCOUNT_COLUMNS
+	))?;
+	let rows = stmt.query_map(params![user_id as i64], LinkedAccount::from_row)?;
+
+	rows.collect()
+}
+
+/// Accounts linked to a discord user, primary first and then in the order they were linked
+pub async fn get_linked_accounts(user_id: u64) -> Result<Vec<LinkedAccount>, Error> {
+	with_users_db(move |conn| query_linked_accounts(conn, user_id)).await
+}
+
+/// Link an account, or update its ign if it is already linked.
+/// It becomes the primary account if `make_primary` is set or the user had none yet
+pub async fn add_linked_account(
+	user_id: u64,
+	username: &str,
+	account: LinkedAccount,
+	make_primary: bool,
+	audit: Option<LinkAudit>,
+) -> Result<LinkedAccount, Error> {
+	let username = username.to_string();
+	with_users_db(move |conn| {
+		let tx = conn.transaction()?;
+		tx.execute(
+			"INSERT INTO users (id, username) VALUES (?1, ?2) ON CONFLICT(id) DO NOTHING",
+			params![user_id as i64, username],
+		)?;
+
+		let accounts = query_linked_accounts(&tx, user_id)?;
+		let previous = accounts
+			.iter()
+			.find(|account| account.primary)
+			.map(|account| account.mc_uuid.clone());
+		let primary = make_primary || previous.is_none();
+		rename_minecraft_account(&tx, &account.mc_uuid, &account.mc_username)?;
+		if primary {
+			tx.execute(
+				"UPDATE linked_accounts SET is_primary = 0 WHERE user_id = ?1",
+				params![user_id as i64],
+			)?;
+		}
+
+		tx.execute(
+			"INSERT INTO linked_accounts (user_id, mc_uuid, mc_username, is_primary, verified_at)
+				VALUES (?1, ?2, ?3, ?4, ?5)
+				ON CONFLICT(user_id, mc_uuid) DO UPDATE SET
+					mc_username = excluded.mc_username,
+					is_primary = is_primary OR excluded.is_primary,
+					verified_at = COALESCE(excluded.verified_at, verified_at),
+					flagged_at = NULL,
+					flag_reason = NULL",
+			params![
+				user_id as i64,
+				account.mc_uuid,
+				account.mc_username,
+				primary,
+				account.verified_at,
+			],
+		)?;
+
+		let linked = tx.query_row(
+			&format!(
+				"SELECT {} FROM linked_accounts WHERE user_id = ?1 AND mc_uuid = ?2",
+				LINKED_ACCOUNT_COLUMNS
+			),
+			params![user_id as i64, account.mc_uuid],
+			LinkedAccount::from_row,
+		)?;
+		if let Some(audit) = audit {
+			record_link_audit(&tx, audit, user_id, previous.as_deref(), Some(&linked))?;
+		}
+		tx.commit()?;
+		Ok(linked)
+	})
+	.await
+}
+
+/// Update the ign of every link to `mc_uuid` and record the old one in `name_history`.
+/// Returns whether the name changed
+fn rename_minecraft_account(
+	conn: &Connection,
+	mc_uuid: &str,
+	mc_username: &str,
+) -> Result<bool> {
+	let previous: Vec<String> = conn
+		.prepare(
+			"SELECT DISTINCT mc_username FROM linked_accounts
+				WHERE mc_uuid = ?1 AND mc_username != ?2",
+		)?
+		.query_map(params![mc_uuid, mc_username], |row| row.get(0))?
+		.collect::<Result<_>>()?;
+	if previous.is_empty() {
+		return Ok(false);
+	}
+
+	let now = Utc::now().timestamp();
+	for previous_username in &previous {
+		conn.execute(
+			"INSERT INTO name_history (mc_uuid, previous_username, mc_username, changed_at)
+				VALUES (?1, ?2, ?3, ?4)",
+			params![mc_uuid, previous_username, mc_username, now],
+		)?;
+	}
+	conn.execute(
+		"UPDATE linked_accounts SET mc_username = ?2 WHERE mc_uuid = ?1",
+		params![mc_uuid, mc_username],
+	)?;
+	Ok(true)
+}
+
+/// Linked accounts not checked in the last `max_age` seconds, oldest check first,
+/// with the discord username they have to be linked to on hypixel
+pub async fn get_stale_linked_accounts(
+	max_age: i64,
+	limit: usize,
+) -> Result<Vec<(LinkedAccount, String)>, Error> {
+	let checked_before = Utc::now().timestamp() - max_age;
+	with_users_db(move |conn| {
+		let columns = LINKED_ACCOUNT_COLUMNS
+			.split(", ")
+			.map(|column| format!("linked_accounts.{}", column))
+			.collect::<Vec<_>>()
+			.join(", ");
+		let mut stmt = conn.prepare(&format!(
+			"SELECT {}, users.username FROM linked_accounts
+				JOIN users ON users.id = linked_accounts.user_id
+				WHERE checked_at IS NULL OR checked_at < ?1
+				ORDER BY checked_at IS NOT NULL, checked_at
+				LIMIT ?2",
+			columns
+		))?;
+		let rows = stmt.query_map(params![checked_before, limit as i64], |row| {
+			Ok((LinkedAccount::from_row(row)?, row.get(6)?))
+		})?;
+
+		rows.collect()
+	})
+	.await
+}
+
+/// Store the result of checking a linked account. The ign is updated for every link to the
+/// account, the flag only for this discord user's link and cleared if `flag_reason` is `None`.
+/// Returns whether the ign changed
+pub async fn save_linked_account_check(
+	user_id: u64,
+	mc_uuid: &str,
+	mc_username: &str,
+	flag_reason: Option<String>,
+) -> Result<bool, Error> {
+	let mc_uuid = mc_uuid.to_string();
+	let mc_username = mc_username.to_string();
+	with_users_db(move |conn| {
+		let tx = conn.transaction()?;
+		let renamed = rename_minecraft_account(&tx, &mc_uuid, &mc_username)?;
+		tx.execute(
+			"UPDATE linked_accounts SET
+				checked_at = ?3,
+				flagged_at = CASE WHEN ?4 IS NULL THEN NULL ELSE COALESCE(flagged_at, ?3) END,
+				flag_reason = ?4
+				WHERE user_id = ?1 AND mc_uuid = ?2",
+			params![user_id as i64, mc_uuid, Utc::now().timestamp(), flag_reason],
+		)?;
+		tx.commit()?;
+		Ok(renamed)
+	})
+	.await
+}
+
+/// Make an already linked account the primary one, returns false if it isn't linked
+pub async fn set_primary_account(
+	user_id: u64,
+	mc_uuid: &str,
+) -> Result<bool, Error> {
+	let mc_uuid = mc_uuid.to_string();
+	with_users_db(move |conn| {
+		let tx = conn.transaction()?;
+		let linked = tx
+			.prepare("SELECT 1 FROM linked_accounts WHERE user_id = ?1 AND mc_uuid = ?2")?
+			.exists(params![user_id as i64, mc_uuid])?;
+		if linked {
+			tx.execute(
+				"UPDATE linked_accounts SET is_primary = (mc_uuid = ?2) WHERE user_id = ?1",
+				params![user_id as i64, mc_uuid],
+			)?;
+		}
+		tx.commit()?;
+		Ok(linked)
+	})
+	.await
+}
+
+/// Unlink one account, or every account if `mc_uuid` is `None`, and return the removed ones.
+/// If the primary account is removed the oldest remaining one takes its place
+pub async fn remove_linked_accounts(
+	user_id: u64,
+	mc_uuid: Option<&str>,
+	audit: Option<LinkAudit>,
+) -> Result<Vec<LinkedAccount>, Error> {
+	let mc_uuid = mc_uuid.map(ToOwned::to_owned);
+	with_users_db(move |conn| {
+		let tx = conn.transaction()?;
+		let removed: Vec<LinkedAccount> = query_linked_accounts(&tx, user_id)?
+			.into_iter()
+			.filter(|account| mc_uuid.as_ref().is_none_or(|uuid| *uuid == account.mc_uuid))
+			.collect();
+
+		for account in &removed {
+			tx.execute(
+				"DELETE FROM linked_accounts WHERE user_id = ?1 AND mc_uuid = ?2",
+				params![user_id as i64, account.mc_uuid],
+			)?;
+			if let Some(audit) = audit {
+				record_link_audit(&tx, audit, user_id, Some(&account.mc_uuid), None)?;
+			}
+		}
+
+		if removed.iter().any(|account| account.primary) {
+			tx.execute(
+				"UPDATE linked_accounts SET is_primary = 1 WHERE rowid = (
+					SELECT MIN(rowid) FROM linked_accounts WHERE user_id = ?1
+				)",
+				params![user_id as i64],
+			)?;
+		}
+		tx.commit()?;
+		Ok(removed)
+	})
+	.await
+}
+
+/// Weekly gexp requirement a discord server has registered for a hypixel guild
+#[derive(Clone)]
+pub struct GuildRequirement {
+	pub discord_guild_id: u64,
+	pub hypixel_guild_id: String,
+	pub hypixel_guild_name: String,
+	pub gexp: i64,
+	pub channel_id: u64,
+	/// unix timestamp of the last scheduled report
+	pub last_report: i64,
+}
+
+impl GuildRequirement {
+	fn from_row(row: &rusqlite::Row) -> Result<Self> {
+		Ok(GuildRequirement {
+			discord_guild_id: row.get::<_, i64>(0)? as u64,
+			hypixel_guild_id: row.get(1)?,
+			hypixel_guild_name: row.get(2)?,
+			gexp: row.get(3)?,
+			channel_id: row.get::<_, i64>(4)? as u64,
+			last_report: row.get(5)?,
+		})
+	}
+}
+
+pub async fn set_guild_requirement(requirement: &GuildRequirement) -> Result<(), Error> {
+	let requirement = requirement.clone();
+	with_users_db(move |conn| {
+		conn.execute(
+			"INSERT OR REPLACE INTO guild_requirements
+				(discord_guild_id, hypixel_guild_id, hypixel_guild_name, gexp, channel_id, last_report)
+				VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+			params![
+				requirement.discord_guild_id as i64,
+				requirement.hypixel_guild_id,
+				requirement.hypixel_guild_name,
+				requirement.gexp,
+				requirement.channel_id as i64,
+				requirement.last_report,
+			],
+		)?;
+		Ok(())
+	})
+	.await
+}
+
+pub async fn remove_guild_requirement(discord_guild_id: u64) -> Result<bool, Error> {
+	with_users_db(move |conn| {
+		let modified = conn.execute(
+			"DELETE FROM guild_requirements WHERE discord_guild_id = ?1",
+			params![discord_guild_id as i64],
+		)?;
+		Ok(modified != 0)
+	})
+	.await
+}
+
+pub async fn get_guild_requirement(
+	discord_guild_id: u64
+) -> Result<Option<GuildRequirement>, Error> {
+	with_users_db(move |conn| {
+		conn.query_row(
+			"SELECT discord_guild_id, hypixel_guild_id, hypixel_guild_name, gexp, channel_id, last_report
+				FROM guild_requirements WHERE discord_guild_id = ?1",
+			params![discord_guild_id as i64],
+			GuildRequirement::from_row,
+		)
+		.optional()
+	})
+	.await
+}
+
+pub async fn get_guild_requirements() -> Result<Vec<GuildRequirement>, Error> {
+	with_users_db(|conn| {
+		let mut stmt = conn.prepare(
+			"SELECT discord_guild_id, hypixel_guild_id, hypixel_guild_name, gexp, channel_id, last_report
+				FROM guild_requirements",
+		)?;
+		let rows = stmt.query_map([], GuildRequirement::from_row)?;
+
+		rows.collect()
+	})
+	.await
+}
+
+pub async fn set_guild_requirement_last_report(
+	discord_guild_id: u64,
+	last_report: i64,
+) -> Result<(), Error> {
+	with_users_db(move |conn| {
+		conn.execute(
+			"UPDATE guild_requirements SET last_report = ?1 WHERE discord_guild_id = ?2",
+			params![last_report, discord_guild_id as i64],
+		)?;
+		Ok(())
+	})
+	.await
+}
+
+/// Role, and optionally a nickname, given to members of a server once they link an account
+#[derive(Debug, Clone)]
+pub struct VerificationSettings {
+	pub discord_guild_id: u64,
+	pub role_id:          u64,
+	/// `{ign}` and `{name}` are replaced with the primary ign and discord name
+	pub nickname_format:  Option<String>,
+}
+
+pub async fn set_verification_settings(settings: &VerificationSettings) -> Result<(), Error> {
+	let settings = settings.clone();
+	with_users_db(move |conn| {
+		conn.execute(
+			"INSERT OR REPLACE INTO verification_settings (discord_guild_id, role_id, nickname_format)
+				VALUES (?1, ?2, ?3)",
+			params![
+				settings.discord_guild_id as i64,
+				settings.role_id as i64,
+				settings.nickname_format,
+			],
+		)?;
+		Ok(())
+	})
+	.await
+}
+
+pub async fn remove_verification_settings(discord_guild_id: u64) -> Result<bool, Error> {
+	with_users_db(move |conn| {
+		let modified = conn.execute(
+			"DELETE FROM verification_settings WHERE discord_guild_id = ?1",
+			params![discord_guild_id as i64],
+		)?;
+		Ok(modified != 0)
+	})
+	.await
+}
+
+impl VerificationSettings {
+	fn from_row(row: &rusqlite::Row) -> Result<Self> {
+		Ok(VerificationSettings {
+			discord_guild_id: row.get::<_, i64>(0)? as u64,
+			role_id:          row.get::<_, i64>(1)? as u64,
+			nickname_format:  row.get(2)?,
+		})
+	}
+}
+
+pub async fn get_verification_settings(
+	discord_guild_id: u64
+) -> Result<Option<VerificationSettings>, Error> {
+	with_users_db(move |conn| {
+		conn.query_row(
+			"SELECT discord_guild_id, role_id, nickname_format
+				FROM verification_settings WHERE discord_guild_id = ?1",
+			params![discord_guild_id as i64],
+			VerificationSettings::from_row,
+		)
+		.optional()
+	})
+	.await
+}
+
+/// Settings of every server that has a verified role set
+pub async fn get_all_verification_settings() -> Result<Vec<VerificationSettings>, Error> {
+	with_users_db(|conn| {
+		let mut stmt = conn.prepare(
+			"SELECT discord_guild_id, role_id, nickname_format FROM verification_settings",
+		)?;
+		let rows = stmt.query_map([], VerificationSettings::from_row)?;
+		rows.collect()
+	})
+	.await
+}
+
+/// The primary linked account of every discord user, keyed by discord id
+pub async fn get_primary_accounts() -> Result<HashMap<u64, LinkedAccount>, Error> {
+	with_users_db(|conn| {
+		let mut stmt = conn.prepare(&format!(
+			"SELECT {} FROM linked_accounts WHERE is_primary = 1",
+			LINKED_ACCOUNT_COLUMNS
+		))?;
+		let rows = stmt.query_map([], LinkedAccount::from_row)?;
+
+		rows.map(|account| account.map(|account| (account.user_id, account)))
+			.collect()
+	})
+	.await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// `users.db` as `create_users_table` left it before migrations existed
+	fn legacy_users_db() -> Connection {
+		let conn = Connection::open_in_memory().unwrap();
+		conn.execute_batch(
+			"CREATE TABLE users (
+				id INTEGER PRIMARY KEY,
+				username TEXT NOT NULL,
+				mc_username TEXT,
+				mc_uuid TEXT,
+				color TEXT
+				);
+			INSERT INTO users (id, username, mc_username, mc_uuid, color)
+				VALUES (1234, 'cow', 'Not_a_cow', 'uuid1', '0x2b2d31');",
+		)
+		.unwrap();
+		conn
+	}
+
+	fn schema_version(conn: &Connection) -> i64 {
+		conn.query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+			row.get(0)
+		})
+		.unwrap()
+	}
+
+	#[test]
+	fn migrations_are_ordered() {
+		for migrations in [USERS_MIGRATIONS, TAGS_MIGRATIONS] {
+			assert!(
+				migrations
+					.windows(2)
+					.all(|pair| pair[0].version < pair[1].version)
+			);
+		}
+	}
+
+	#[test]
+	fn profile_updates_only_change_their_setting() {
+		let mut conn = legacy_users_db();
+		run_migrations(&mut conn, USERS_MIGRATIONS).unwrap();
+
+		update_user_profile_in(&mut conn, 1234, "cow", |profile| profile.private = true).unwrap();
+		let profile = update_user_profile_in(&mut conn, 1234, "cow2", |profile| {
+			profile.uptime_window = Some(30)
+		})
+		.unwrap();
+
+		assert!(profile.private);
+		assert_eq!(profile.uptime_window, Some(30));
+		assert_eq!(profile.username, "cow2");
+		assert_eq!(profile.color, Some(0x2b2d31));
+
+		let created = update_user_profile_in(&mut conn, 5678, "dog", |_| {}).unwrap();
+		assert_eq!((created.id, created.private), (5678, false));
+	}
+
+	#[test]
+	fn migrates_legacy_users_db() {
+		let mut conn = legacy_users_db();
+
+		let version = run_migrations(&mut conn, USERS_MIGRATIONS).unwrap();
+		assert_eq!(version, USERS_MIGRATIONS.last().unwrap().version);
+		assert_eq!(schema_version(&conn), version);
+
+		let (username, mc_uuid, timezone, private): (String, String, Option<String>, bool) = conn
+			.query_row(
+				"SELECT username, mc_uuid, timezone, private FROM users WHERE id = 1234",
+				[],
+				|row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+			)
+			.unwrap();
+		assert_eq!(
+			(username.as_str(), mc_uuid.as_str(), timezone, private),
+			("cow", "uuid1", None, false)
+		);
+		conn.prepare("SELECT * FROM guild_requirements").unwrap();
+
+		let accounts = query_linked_accounts(&conn, 1234).unwrap();
+		assert_eq!(accounts.len(), 1);
+		assert_eq!(accounts[0].mc_username, "Not_a_cow");
+		assert!(accounts[0].primary);
+
+		// running again is a no-op
+		assert_eq!(
+			run_migrations(&mut conn, USERS_MIGRATIONS).unwrap(),
+			version
+		);
+		let applied: i64 = conn
+			.query_row("SELECT COUNT(*) FROM schema_version", [], |row| row.get(0))
+			.unwrap();
+		assert_eq!(applied, USERS_MIGRATIONS.len() as i64);
+	}
+
+	#[test]
+	fn selects_accounts_by_position_name_or_uuid() {
+		let accounts: Vec<LinkedAccount> = [("uuid1", "Not_a_cow"), ("uuid2", "cow_alt")]
+			.iter()
+			.enumerate()
+			.map(|(index, (uuid, name))| LinkedAccount {
+				user_id:     1234,
+				mc_uuid:     uuid.to_string(),
+				mc_username: name.to_string(),
+				primary:     index == 0,
+				verified_at: None,
+				flag_reason: None,
+			})
+			.collect();
+		let selected =
+			|selector| select_account(&accounts, selector).map(|account| &account.mc_uuid);
+
+		assert_eq!(selected("2").unwrap(), "uuid2");
+		assert_eq!(selected("COW_ALT").unwrap(), "uuid2");
+		assert_eq!(selected("uuid1").unwrap(), "uuid1");
+		assert!(selected("0").is_none());
+		assert!(selected("3").is_none());
+		assert!(selected("someone").is_none());
+	}
+
+	#[test]
+	fn renames_keep_name_history() {
+		let mut conn = legacy_users_db();
+		run_migrations(&mut conn, USERS_MIGRATIONS).unwrap();
+		conn.execute(
+			"INSERT INTO linked_accounts (user_id, mc_uuid, mc_username) VALUES (5678, 'uuid1', 'Not_a_cow')",
+			[],
+		)
+		.unwrap();
+
+		assert!(!rename_minecraft_account(&conn, "uuid1", "Not_a_cow").unwrap());
+		assert!(rename_minecraft_account(&conn, "uuid1", "Now_a_cow").unwrap());
+
+		let names: Vec<String> = conn
+			.prepare("SELECT DISTINCT mc_username FROM linked_accounts WHERE mc_uuid = 'uuid1'")
+			.unwrap()
+			.query_map([], |row| row.get(0))
+			.unwrap()
+			.collect::<Result<_>>()
+			.unwrap();
+		assert_eq!(names, vec!["Now_a_cow".to_string()]);
+
+		let history: (String, String) = conn
+			.query_row(
+				"SELECT previous_username, mc_username FROM name_history WHERE mc_uuid = 'uuid1'",
+				[],
+				|row| Ok((row.get(0)?, row.get(1)?)),
+			)
+			.unwrap();
+		assert_eq!(history, ("Not_a_cow".to_string(), "Now_a_cow".to_string()));
+	}
+
+	#[test]
+	fn adopts_columns_added_before_migrations() {
+		let mut conn = legacy_users_db();
+		conn.execute("ALTER TABLE users ADD COLUMN timezone TEXT", [])
+			.unwrap();
+
+		run_migrations(&mut conn, USERS_MIGRATIONS).unwrap();
+		conn.prepare("SELECT timezone, uptime_window, private FROM users")
+			.unwrap();
+	}
+
+	#[tokio::test]
+	async fn pool_handles_concurrent_writes() {
+		let path = std::env::temp_dir().join(format!("cow_bot_pool_{}.db", std::process::id()));
+		let pool = create_pool(&path).unwrap();
+
+		let mode: String = with_connection(&pool, |conn| {
+			conn.execute("CREATE TABLE IF NOT EXISTS writes (n INTEGER)", [])?;
+			conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))
+		})
+		.await
+		.unwrap();
+		assert_eq!(mode, "wal");
+
+		let writes = (0..50).map(|n| {
+			with_connection(&pool, move |conn| {
+				conn.execute("INSERT INTO writes (n) VALUES (?1)", params![n])
+			})
+		});
+		futures::future::try_join_all(writes).await.unwrap();
+
+		let count: i64 = with_connection(&pool, |conn| {
+			conn.query_row("SELECT COUNT(*) FROM writes", [], |row| row.get(0))
+		})
+		.await
+		.unwrap();
+		assert_eq!(count, 50);
+
+		drop(pool);
+		for suffix in ["", "-wal", "-shm"] {
+			let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+		}
+	}
+
+	#[test]
+	fn merges_guild_tag_tables() {
+		let mut conn = Connection::open_in_memory().unwrap();
+		conn.execute_batch(
+			"CREATE TABLE tags_1 (name TEXT PRIMARY KEY, content TEXT NOT NULL);
+			CREATE TABLE tags_2 (name TEXT PRIMARY KEY, content TEXT NOT NULL);
+			INSERT INTO tags_1 VALUES ('rules', 'be nice'), ('faq', 'read the rules');
+			INSERT INTO tags_2 VALUES ('rules', 'no rules');",
+		)
+		.unwrap();
+
+		run_migrations(&mut conn, TAGS_MIGRATIONS).unwrap();
+
+		let content: String = conn
+			.query_row(
+				"SELECT content FROM tags WHERE guild_id = 2 AND name = 'rules'",
+				[],
+				|row| row.get(0),
+			)
+			.unwrap();
+		assert_eq!(content, "no rules");
+		let count: i64 = conn
+			.query_row("SELECT COUNT(*) FROM tags WHERE guild_id = 1", [], |row| {
+				row.get(0)
+			})
+			.unwrap();
+		assert_eq!(count, 2);
+
+		let old_tables: Vec<String> = conn
+			.prepare(
+				"SELECT name FROM sqlite_master WHERE type = 'table' AND name LIKE '%tags_%' ORDER BY name",
+			)
+			.unwrap()
+			.query_map([], |row| row.get(0))
+			.unwrap()
+			.collect::<Result<_>>()
+			.unwrap();
+		assert_eq!(old_tables, ["legacy_tags_1", "legacy_tags_2"]);
+	}
+
+	#[test]
+	fn keeps_guild_tag_tables_that_dont_fully_merge() {
+		let mut conn = Connection::open_in_memory().unwrap();
+		conn.execute_batch(
+			"CREATE TABLE tags (
+				guild_id INTEGER NOT NULL,
+				name TEXT NOT NULL,
+				content TEXT NOT NULL,
+				PRIMARY KEY (guild_id, name)
+				);
+			INSERT INTO tags VALUES (1, 'rules', 'already here');
+			CREATE TABLE tags_1 (name TEXT PRIMARY KEY, content TEXT NOT NULL);
+			INSERT INTO tags_1 VALUES ('rules', 'be nice'), ('faq', 'read the rules');",
+		)
+		.unwrap();
+
+		assert!(run_migrations(&mut conn, TAGS_MIGRATIONS).is_err());
+
+		let kept: i64 = conn
+			.query_row("SELECT COUNT(*) FROM tags_1", [], |row| row.get(0))
+			.unwrap();
+		assert_eq!(kept, 2);
+		let merged: i64 = conn
+			.query_row("SELECT COUNT(*) FROM tags", [], |row| row.get(0))
+			.unwrap();
+		assert_eq!(merged, 1);
+	}
+}
@@ -0,0 +1,167 @@
+use bson::oid::ObjectId;
+use bson::{DateTime as BsonDateTime, Document, doc};
+use chrono::{Duration, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::options::{IndexOptions, ReplaceOptions};
+use mongodb::{Client, Collection, IndexModel};
+use serde::{Deserialize, Serialize};
+
+use crate::config::config;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrackedPlayer {
+	#[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+	pub id:           Option<ObjectId>,
+	pub uuid:         String,
+	pub reason:       String,
+	/// discord id of whoever asked for the player to be tracked
+	pub requested_by: String,
+	pub added_at:     BsonDateTime,
+	pub expires_at:   Option<BsonDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrackedGuild {
+	#[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+	pub id:           Option<ObjectId>,
+	pub guild_id:     String,
+	pub name:         String,
+	pub reason:       String,
+	/// discord id of whoever asked for the guild to be tracked
+	pub requested_by: String,
+	pub added_at:     BsonDateTime,
+	pub expires_at:   Option<BsonDateTime>,
+}
+
+pub fn tracked_players(client: &Client) -> Collection<TrackedPlayer> {
+	let config = config();
+	config
+		.database(client)
+		.collection(&config.tracked_players_collection)
+}
+
+pub fn tracked_guilds(client: &Client) -> Collection<TrackedGuild> {
+	let config = config();
+	config
+		.database(client)
+		.collection(&config.tracked_guilds_collection)
+}
+
+pub async fn create_tracking_indexes(client: &Client) -> Result<(), mongodb::error::Error> {
+	let unique = IndexOptions::builder().unique(true).build();
+	tracked_players(client)
+		.create_index(
+			IndexModel::builder()
+				.keys(doc! { "uuid": 1 })
+				.options(unique.clone())
+				.build(),
+		)
+		.await?;
+	tracked_guilds(client)
+		.create_index(
+			IndexModel::builder()
+				.keys(doc! { "guild_id": 1 })
+				.options(unique)
+				.build(),
+		)
+		.await?;
+	Ok(())
+}
+
+/// longest tracking period that can be picked, longer ones should track forever instead
+pub const MAX_TRACKING_DAYS: i64 = 3650;
+
+/// `expires_in` is in days, clamped to `MAX_TRACKING_DAYS`, `None` tracks forever
+pub fn expiry_from_days(expires_in: Option<i64>) -> Option<BsonDateTime> {
+	expires_in.map(|days| {
+		BsonDateTime::from_chrono(Utc::now() + Duration::days(days.clamp(0, MAX_TRACKING_DAYS)))
+	})
+}
+
+fn not_expired() -> Document {
+	doc! {
+		"$or": [
+			{ "expires_at": null },
+			{ "expires_at": { "$gt": BsonDateTime::now() } },
+		]
+	}
+}
+
+pub async fn track_player(
+	client: &Client,
+	player: &TrackedPlayer,
+) -> Result<(), mongodb::error::Error> {
+	tracked_players(client)
+		.replace_one(doc! { "uuid": &player.uuid }, player)
+		.with_options(ReplaceOptions::builder().upsert(true).build())
+		.await?;
+	Ok(())
+}
+
+pub async fn track_guild(
+	client: &Client,
+	guild: &TrackedGuild,
+) -> Result<(), mongodb::error::Error> {
+	tracked_guilds(client)
+		.replace_one(doc! { "guild_id": &guild.guild_id }, guild)
+		.with_options(ReplaceOptions::builder().upsert(true).build())
+		.await?;
+	Ok(())
+}
+
+pub async fn untrack_player(
+	client: &Client,
+	uuid: &str,
+) -> Result<bool, mongodb::error::Error> {
+	let result = tracked_players(client)
+		.delete_one(doc! { "uuid": uuid })
+		.await?;
+	Ok(result.deleted_count > 0)
+}
+
+pub async fn untrack_guild(
+	client: &Client,
+	guild_id: &str,
+) -> Result<bool, mongodb::error::Error> {
+	let result = tracked_guilds(client)
+		.delete_one(doc! { "guild_id": guild_id })
+		.await?;
+	Ok(result.deleted_count > 0)
+}
+
+pub async fn is_player_tracked(
+	client: &Client,
+	uuid: &str,
+) -> Result<bool, mongodb::error::Error> {
+	let mut filter = not_expired();
+	filter.insert("uuid", uuid);
+	Ok(tracked_players(client).count_documents(filter).await? > 0)
+}
+
+pub async fn get_tracked_players(
+	client: &Client
+) -> Result<Vec<TrackedPlayer>, mongodb::error::Error> {
+	tracked_players(client)
+		.find(not_expired())
+		.await?
+		.try_collect()
+		.await
+}
+
+pub async fn get_tracked_guilds(
+	client: &Client
+) -> Result<Vec<TrackedGuild>, mongodb::error::Error> {
+	tracked_guilds(client)
+		.find(not_expired())
+		.await?
+		.try_collect()
+		.await
+}
+
+/// Delete every subscription whose expiry has passed
+pub async fn remove_expired(client: &Client) -> Result<u64, mongodb::error::Error> {
+	let expired = doc! { "expires_at": { "$lte": BsonDateTime::now() } };
+	let players = tracked_players(client).delete_many(expired.clone()).await?;
+	let guilds = tracked_guilds(client).delete_many(expired).await?;
+	Ok(players.deleted_count + guilds.deleted_count)
+}
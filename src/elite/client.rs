@@ -0,0 +1,181 @@
+use std::fmt;
+
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+
+use crate::elite::responses::{CollectionsDataPoint, SelectedProfile, SkillsDataPoint};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.elitebot.dev";
+
+#[derive(Debug)]
+pub enum EliteError {
+	Request(reqwest::Error),
+	Decode(serde_json::Error),
+	Status(StatusCode),
+}
+
+impl std::error::Error for EliteError {}
+
+impl fmt::Display for EliteError {
+	fn fmt(
+		&self,
+		f: &mut fmt::Formatter<'_>,
+	) -> fmt::Result {
+		match self {
+			| EliteError::Request(e) => write!(f, "Request to the Elite API failed: {}", e),
+			| EliteError::Decode(e) => write!(f, "Invalid response from the Elite API: {}", e),
+			| EliteError::Status(status) => write!(f, "Elite API responded with {}", status),
+		}
+	}
+}
+
+impl From<reqwest::Error> for EliteError {
+	fn from(err: reqwest::Error) -> EliteError { EliteError::Request(err) }
+}
+
+impl From<serde_json::Error> for EliteError {
+	fn from(err: serde_json::Error) -> EliteError { EliteError::Decode(err) }
+}
+
+/// Client for the collection and skill graphs on api.elitebot.dev
+#[derive(Debug)]
+pub struct EliteClient {
+	http:     reqwest::Client,
+	base_url: String,
+}
+
+impl EliteClient {
+	pub fn new(base_url: &str) -> Self {
+		EliteClient {
+			http:     reqwest::Client::new(),
+			base_url: base_url.trim_end_matches('/').to_string(),
+		}
+	}
+
+	/// Returns `None` when the api has nothing for this path, eg a player without profiles
+	async fn get<T: DeserializeOwned>(
+		&self,
+		path: &str,
+		query: &[(&str, String)],
+	) -> Result<Option<T>, EliteError> {
+		let response = self
+			.http
+			.get(format!("{}{}", self.base_url, path))
+			.query(query)
+			.send()
+			.await?;
+
+		match response.status() {
+			| StatusCode::NOT_FOUND => Ok(None),
+			| status if !status.is_success() => Err(EliteError::Status(status)),
+			| _ => Ok(Some(serde_json::from_str(&response.text().await?)?)),
+		}
+	}
+
+	pub async fn get_selected_profile(
+		&self,
+		uuid: &str,
+	) -> Result<Option<String>, EliteError> {
+		let profile: Option<SelectedProfile> = self
+			.get(&format!("/profiles/{}/selected", uuid), &[])
+			.await?;
+		Ok(profile.map(|profile| profile.profile_id))
+	}
+
+	/// Collection totals over `days` days starting at the unix timestamp `from`
+	pub async fn get_collection_graph(
+		&self,
+		uuid: &str,
+		profile_id: &str,
+		from: i64,
+		days: i64,
+	) -> Result<Vec<CollectionsDataPoint>, EliteError> {
+		let points = self
+			.get(
+				&format!("/graph/{}/{}/collections", uuid, profile_id),
+				&[("from", from.to_string()), ("days", days.to_string())],
+			)
+			.await?;
+		Ok(points.unwrap_or_default())
+	}
+
+	/// Skill xp over `days` days starting at the unix timestamp `from`
+	pub async fn get_skill_graph(
+		&self,
+		uuid: &str,
+		profile_id: &str,
+		from: i64,
+		days: i64,
+	) -> Result<Vec<SkillsDataPoint>, EliteError> {
+		let points = self
+			.get(
+				&format!("/graph/{}/{}/skills", uuid, profile_id),
+				&[("from", from.to_string()), ("days", days.to_string())],
+			)
+			.await?;
+		Ok(points.unwrap_or_default())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+	use wiremock::matchers::{path, query_param};
+	use wiremock::{Mock, MockServer, ResponseTemplate};
+
+	use super::*;
+
+	#[tokio::test]
+	async fn fetches_collection_graph_for_selected_profile() {
+		let server = MockServer::start().await;
+		Mock::given(path("/profiles/player1/selected"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+				"profileId": "profile1",
+				"profileName": "Cucumber"
+			})))
+			.mount(&server)
+			.await;
+		Mock::given(path("/graph/player1/profile1/collections"))
+			.and(query_param("from", "1700000000"))
+			.and(query_param("days", "7"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(json!([
+				{ "timestamp": 1700000000, "collections": { "WHEAT": 100 } },
+				{ "timestamp": 1700086400, "collections": { "WHEAT": 250 } }
+			])))
+			.mount(&server)
+			.await;
+
+		let elite = EliteClient::new(&server.uri());
+		let profile = elite
+			.get_selected_profile("player1")
+			.await
+			.unwrap()
+			.unwrap();
+		let points = elite
+			.get_collection_graph("player1", &profile, 1_700_000_000, 7)
+			.await
+			.unwrap();
+
+		assert_eq!(profile, "profile1");
+		assert_eq!(points.len(), 2);
+		assert_eq!(points[1].collections["WHEAT"], 250);
+	}
+
+	#[tokio::test]
+	async fn missing_profile_is_none() {
+		let server = MockServer::start().await;
+		Mock::given(path("/profiles/player1/selected"))
+			.respond_with(ResponseTemplate::new(404))
+			.mount(&server)
+			.await;
+
+		let elite = EliteClient::new(&server.uri());
+		assert!(
+			elite
+				.get_selected_profile("player1")
+				.await
+				.unwrap()
+				.is_none()
+		);
+	}
+}
@@ -0,0 +1,26 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct SelectedProfile {
+	#[serde(rename = "profileId")]
+	pub profile_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CollectionsDataPoint {
+	/// unix timestamp in seconds
+	pub timestamp:   i64,
+	#[serde(default)]
+	pub collections: HashMap<String, i64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SkillsDataPoint {
+	/// unix timestamp in seconds
+	pub timestamp: i64,
+	/// total xp per skill
+	#[serde(default)]
+	pub skills:    HashMap<String, f64>,
+}
@@ -0,0 +1,313 @@
+use std::fmt;
+use std::time::Duration;
+
+use reqwest::header::HeaderMap;
+use reqwest::{Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::hypixel::rate_limit::{Priority, RateLimiter};
+use crate::hypixel::responses::{Guild, GuildResponse, Player, PlayerResponse};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.hypixel.net";
+const MAX_RETRIES: u32 = 3;
+
+pub enum GuildLookup<'a> {
+	Id(&'a str),
+	Player(&'a str),
+	Name(&'a str),
+}
+
+#[derive(Debug)]
+pub enum HypixelError {
+	Request(reqwest::Error),
+	Decode(serde_json::Error),
+	Status(StatusCode),
+	InvalidKey,
+	RateLimited,
+	Api(String),
+}
+
+impl std::error::Error for HypixelError {}
+
+impl fmt::Display for HypixelError {
+	fn fmt(
+		&self,
+		f: &mut fmt::Formatter<'_>,
+	) -> fmt::Result {
+		match self {
+			| HypixelError::Request(e) => write!(f, "Request to Hypixel failed: {}", e),
+			| HypixelError::Decode(e) => write!(f, "Invalid response from Hypixel: {}", e),
+			| HypixelError::Status(status) => write!(f, "Hypixel responded with {}", status),
+			| HypixelError::InvalidKey => write!(f, "Invalid Hypixel API key"),
+			| HypixelError::RateLimited => write!(f, "Hypixel API key is rate limited"),
+			| HypixelError::Api(cause) => write!(f, "{}", cause),
+		}
+	}
+}
+
+impl From<reqwest::Error> for HypixelError {
+	fn from(err: reqwest::Error) -> HypixelError { HypixelError::Request(err) }
+}
+
+impl From<serde_json::Error> for HypixelError {
+	fn from(err: serde_json::Error) -> HypixelError { HypixelError::Decode(err) }
+}
+
+/// Shared client for every Hypixel API call the bot makes
+pub struct HypixelClient {
+	http:         reqwest::Client,
+	base_url:     String,
+	api_key:      String,
+	rate_limiter: RateLimiter,
+}
+
+impl fmt::Debug for HypixelClient {
+	fn fmt(
+		&self,
+		f: &mut fmt::Formatter<'_>,
+	) -> fmt::Result {
+		// keep the api key out of logs and panic messages
+		f.debug_struct("HypixelClient")
+			.field("base_url", &self.base_url)
+			.finish_non_exhaustive()
+	}
+}
+
+impl HypixelClient {
+	pub fn new(
+		api_key: &str,
+		base_url: &str,
+		key_limit: u32,
+	) -> Self {
+		HypixelClient {
+			http:         reqwest::Client::new(),
+			base_url:     base_url.trim_end_matches('/').to_string(),
+			api_key:      api_key.to_string(),
+			rate_limiter: RateLimiter::new(key_limit),
+		}
+	}
+
+	/// Send a request once the rate limiter allows it, retrying with backoff on 429s
+	async fn send(
+		&self,
+		path: &str,
+		query: &[(&str, &str)],
+		priority: Priority,
+	) -> Result<Response, HypixelError> {
+		let mut attempt = 0;
+
+		loop {
+			self.rate_limiter.acquire(priority).await;
+
+			let response = self
+				.http
+				.get(format!("{}{}", self.base_url, path))
+				.header("API-Key", &self.api_key)
+				.query(query)
+				.send()
+				.await?;
+
+			let headers = response.headers();
+			let reset = header_value(headers, "RateLimit-Reset");
+			self.rate_limiter
+				.update(header_value(headers, "RateLimit-Remaining"), reset);
+
+			if response.status() != StatusCode::TOO_MANY_REQUESTS || attempt >= MAX_RETRIES {
+				return Ok(response);
+			}
+
+			let backoff = reset
+				.or_else(|| header_value(headers, "Retry-After"))
+				.map_or(Duration::from_secs(2u64.pow(attempt)), Duration::from_secs);
+			println!(
+				"\x1b[33m[WARN] Hypixel rate limited {}, retrying in {}s\x1b[0m",
+				path,
+				backoff.as_secs()
+			);
+			self.rate_limiter.block_for(backoff);
+			attempt += 1;
+		}
+	}
+
+	async fn get<T: DeserializeOwned>(
+		&self,
+		path: &str,
+		query: &[(&str, &str)],
+		priority: Priority,
+	) -> Result<T, HypixelError> {
+		let response = self.send(path, query, priority).await?;
+
+		let status = response.status();
+		let body = response.text().await?;
+		let value: Value = serde_json::from_str(&body).map_err(|e| {
+			if status.is_success() {
+				HypixelError::Decode(e)
+			} else {
+				HypixelError::Status(status)
+			}
+		})?;
+
+		if !value["success"].as_bool().unwrap_or(false) {
+			return Err(match status {
+				| StatusCode::FORBIDDEN => HypixelError::InvalidKey,
+				| StatusCode::TOO_MANY_REQUESTS => HypixelError::RateLimited,
+				| _ => HypixelError::Api(
+					value["cause"]
+						.as_str()
+						.unwrap_or("Unknown error")
+						.to_string(),
+				),
+			});
+		}
+
+		Ok(serde_json::from_value(value)?)
+	}
+
+	pub async fn get_guild(
+		&self,
+		lookup: GuildLookup<'_>,
+		priority: Priority,
+	) -> Result<Option<Guild>, HypixelError> {
+		let query = match lookup {
+			| GuildLookup::Id(id) => ("id", id),
+			| GuildLookup::Player(uuid) => ("player", uuid),
+			| GuildLookup::Name(name) => ("name", name),
+		};
+
+		let response: GuildResponse = self.get("/v2/guild", &[query], priority).await?;
+		Ok(response.guild)
+	}
+
+	pub async fn get_player(
+		&self,
+		uuid: &str,
+		priority: Priority,
+	) -> Result<Option<Player>, HypixelError> {
+		let response: PlayerResponse = self.get("/v2/player", &[("uuid", uuid)], priority).await?;
+		Ok(response.player)
+	}
+}
+
+fn header_value<T: std::str::FromStr>(
+	headers: &HeaderMap,
+	name: &str,
+) -> Option<T> {
+	headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+	use wiremock::matchers::{header, method, path, query_param};
+	use wiremock::{Mock, MockServer, ResponseTemplate};
+
+	use super::*;
+
+	#[tokio::test]
+	async fn sends_api_key_and_lookup() {
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/v2/guild"))
+			.and(header("API-Key", "test-key"))
+			.and(query_param("name", "Cow Guild"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+				"success": true,
+				"guild": { "_id": "guild1", "name": "Cow Guild", "members": [] }
+			})))
+			.expect(1)
+			.mount(&server)
+			.await;
+
+		let hypixel = HypixelClient::new("test-key", &server.uri(), 300);
+		let guild = hypixel
+			.get_guild(GuildLookup::Name("Cow Guild"), Priority::Interactive)
+			.await
+			.unwrap()
+			.unwrap();
+
+		assert_eq!(guild.id, "guild1");
+		assert_eq!(guild.name, "Cow Guild");
+	}
+
+	#[tokio::test]
+	async fn maps_error_responses() {
+		let server = MockServer::start().await;
+		Mock::given(path("/v2/player"))
+			.and(query_param("uuid", "forbidden"))
+			.respond_with(ResponseTemplate::new(403).set_body_json(json!({
+				"success": false,
+				"cause": "Invalid API key"
+			})))
+			.mount(&server)
+			.await;
+		Mock::given(path("/v2/player"))
+			.and(query_param("uuid", "throttled"))
+			.respond_with(
+				ResponseTemplate::new(429)
+					.insert_header("RateLimit-Remaining", "0")
+					.insert_header("RateLimit-Reset", "0")
+					.set_body_json(json!({
+						"success": false,
+						"cause": "Key throttle",
+						"throttle": true
+					})),
+			)
+			.expect(MAX_RETRIES as u64 + 1)
+			.mount(&server)
+			.await;
+		Mock::given(path("/v2/player"))
+			.and(query_param("uuid", "broken"))
+			.respond_with(ResponseTemplate::new(502).set_body_string("Bad Gateway"))
+			.mount(&server)
+			.await;
+
+		let hypixel = HypixelClient::new("test-key", &server.uri(), 300);
+
+		assert!(matches!(
+			hypixel.get_player("forbidden", Priority::Interactive).await,
+			Err(HypixelError::InvalidKey)
+		));
+		assert!(matches!(
+			hypixel.get_player("throttled", Priority::Interactive).await,
+			Err(HypixelError::RateLimited)
+		));
+		assert!(matches!(
+			hypixel.get_player("broken", Priority::Interactive).await,
+			Err(HypixelError::Status(StatusCode::BAD_GATEWAY))
+		));
+	}
+
+	#[tokio::test]
+	async fn retries_after_rate_limit() {
+		let server = MockServer::start().await;
+		Mock::given(path("/v2/player"))
+			.respond_with(
+				ResponseTemplate::new(429)
+					.insert_header("RateLimit-Remaining", "0")
+					.insert_header("RateLimit-Reset", "1")
+					.set_body_json(json!({ "success": false, "cause": "Key throttle" })),
+			)
+			.up_to_n_times(1)
+			.mount(&server)
+			.await;
+		Mock::given(path("/v2/player"))
+			.respond_with(
+				ResponseTemplate::new(200)
+					.insert_header("RateLimit-Remaining", "299")
+					.insert_header("RateLimit-Reset", "120")
+					.set_body_json(json!({ "success": true, "player": {} })),
+			)
+			.expect(1)
+			.mount(&server)
+			.await;
+
+		let hypixel = HypixelClient::new("test-key", &server.uri(), 300);
+		let player = hypixel
+			.get_player("player1", Priority::Interactive)
+			.await
+			.unwrap();
+
+		assert!(player.is_some());
+	}
+}
@@ -0,0 +1,3 @@
+pub mod client;
+pub mod rate_limit;
+pub mod responses;
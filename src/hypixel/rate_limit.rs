@@ -0,0 +1,163 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Hypixel counts requests in fixed 5 minute windows
+pub const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+	/// Requests a user is waiting on, these may use the whole bucket
+	Interactive,
+	/// Tracker refreshes, these leave part of the bucket free for interactive requests
+	Background,
+}
+
+/// Token bucket shared by every request made with one api key
+#[derive(Debug)]
+pub struct RateLimiter {
+	capacity:       f64,
+	refill_per_sec: f64,
+	reserved:       f64,
+	bucket:         Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+	tokens:        f64,
+	last_refill:   Instant,
+	blocked_until: Option<Instant>,
+}
+
+impl RateLimiter {
+	pub fn new(key_limit: u32) -> Self {
+		let capacity = key_limit.max(1) as f64;
+		RateLimiter {
+			capacity,
+			refill_per_sec: capacity / RATE_LIMIT_WINDOW.as_secs_f64(),
+			// keep a tenth of the key free for commands
+			reserved: (capacity / 10.0).floor(),
+			bucket: Mutex::new(Bucket {
+				tokens:        capacity,
+				last_refill:   Instant::now(),
+				blocked_until: None,
+			}),
+		}
+	}
+
+	/// Wait until a request with `priority` may be sent
+	pub async fn acquire(
+		&self,
+		priority: Priority,
+	) {
+		let floor = match priority {
+			| Priority::Interactive => 0.0,
+			| Priority::Background => self.reserved,
+		};
+
+		loop {
+			let wait = {
+				let mut bucket = self.bucket.lock().unwrap();
+				let now = Instant::now();
+				self.refill(&mut bucket, now);
+
+				match bucket.blocked_until {
+					| Some(until) if until > now => until - now,
+					| _ => {
+						bucket.blocked_until = None;
+						if bucket.tokens >= floor + 1.0 {
+							bucket.tokens -= 1.0;
+							return;
+						}
+						Duration::from_secs_f64((floor + 1.0 - bucket.tokens) / self.refill_per_sec)
+					},
+				}
+			};
+
+			tokio::time::sleep(wait).await;
+		}
+	}
+
+	/// Sync the bucket with the `RateLimit-Remaining` and `RateLimit-Reset` headers
+	pub fn update(
+		&self,
+		remaining: Option<u32>,
+		reset: Option<u64>,
+	) {
+		let mut bucket = self.bucket.lock().unwrap();
+		let now = Instant::now();
+		self.refill(&mut bucket, now);
+
+		if let Some(remaining) = remaining {
+			bucket.tokens = bucket.tokens.min(remaining as f64);
+
+			if let (0, Some(reset)) = (remaining, reset) {
+				bucket.blocked_until = Some(now + Duration::from_secs(reset));
+			}
+		}
+	}
+
+	/// Stop every request until `duration` has passed, used after a 429
+	pub fn block_for(
+		&self,
+		duration: Duration,
+	) {
+		let mut bucket = self.bucket.lock().unwrap();
+		let until = Instant::now() + duration;
+		bucket.tokens = 0.0;
+		bucket.blocked_until = Some(
+			bucket
+				.blocked_until
+				.map_or(until, |blocked| blocked.max(until)),
+		);
+	}
+
+	fn refill(
+		&self,
+		bucket: &mut Bucket,
+		now: Instant,
+	) {
+		let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+		bucket.tokens = (bucket.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+		bucket.last_refill = now;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test(start_paused = true)]
+	async fn background_leaves_reserve_for_interactive() {
+		let limiter = RateLimiter::new(10);
+
+		for _ in 0..9 {
+			limiter.acquire(Priority::Background).await;
+		}
+
+		let start = Instant::now();
+		limiter.acquire(Priority::Interactive).await;
+		assert_eq!(start.elapsed(), Duration::ZERO);
+
+		// the bucket is empty now, so background has to wait for a token to refill
+		limiter.acquire(Priority::Background).await;
+		assert!(start.elapsed() >= Duration::from_secs(30));
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn empty_headers_block_until_reset() {
+		let limiter = RateLimiter::new(300);
+		limiter.update(Some(0), Some(42));
+
+		let start = Instant::now();
+		limiter.acquire(Priority::Interactive).await;
+		assert!(start.elapsed() >= Duration::from_secs(42));
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn zero_key_limit_does_not_panic() {
+		let limiter = RateLimiter::new(0);
+		limiter.acquire(Priority::Background).await;
+	}
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct GuildResponse {
+	pub guild: Option<Guild>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Guild {
+	#[serde(rename = "_id")]
+	pub id:      String,
+	pub name:    String,
+	pub members: Vec<GuildMember>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GuildMember {
+	pub uuid:        String,
+	/// gexp earned per day, keyed by `YYYY-MM-DD` in Hypixel's timezone
+	#[serde(rename = "expHistory", default)]
+	pub exp_history: HashMap<String, i64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PlayerResponse {
+	pub player: Option<Player>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Player {
+	#[serde(rename = "socialMedia")]
+	pub social_media: Option<SocialMedia>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SocialMedia {
+	#[serde(default)]
+	pub links: Links,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[allow(non_snake_case, dead_code)]
+pub struct Links {
+	pub TWITTER:   Option<String>,
+	pub YOUTUBE:   Option<String>,
+	pub INSTAGRAM: Option<String>,
+	pub TWITCH:    Option<String>,
+	pub DISCORD:   Option<String>,
+	pub FORUMS:    Option<String>,
+}
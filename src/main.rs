@@ -1,21 +1,34 @@
 mod commands;
+mod config;
 mod data;
+mod elite;
+mod hypixel;
 mod tasks;
 
-use std::env::var;
 use std::sync::Arc;
 use std::time::Duration;
 
 use commands::tags::tag_utils::TagDb;
-use data::database::create_users_table;
+use config::Config;
+use data::database::{
+	SqlitePool,
+	create_pool,
+	migrate_tags_db,
+	migrate_users_db,
+	refresh_username,
+};
 use dotenv::dotenv;
+use elite::client::EliteClient;
+use hypixel::client::HypixelClient;
 use mongodb::Client;
 use mongodb::options::ClientOptions;
 use poise::serenity_prelude as serenity;
-use r2d2::Pool;
-use r2d2_sqlite::SqliteConnectionManager;
 use regex::Regex;
-use tasks::update_uptime::uptime_updater;
+use tasks::gexp_requirements::requirement_checker;
+use tasks::refresh_links::link_refresher;
+use tasks::supervisor::{Supervisor, wait_for_signal};
+use tasks::update_gains::{gains_collection, gains_updater};
+use tasks::update_uptime::{uptime_collection, uptime_updater};
 use tokio::sync::OnceCell;
 use types::{Context, Error};
 
@@ -28,22 +41,28 @@ pub struct Data {
 	pub tag_db: Arc<TagDb>,
 }
 
+static CONFIG: OnceCell<Config> = OnceCell::const_new();
 static MONGO_CLIENT: OnceCell<Client> = OnceCell::const_new();
-static API_KEY: OnceCell<String> = OnceCell::const_new();
+static HYPIXEL_CLIENT: OnceCell<HypixelClient> = OnceCell::const_new();
+static ELITE_CLIENT: OnceCell<EliteClient> = OnceCell::const_new();
 static ERROR_COLOR: OnceCell<u32> = OnceCell::const_new();
-static DB_POOL: OnceCell<Pool<SqliteConnectionManager>> = OnceCell::const_new();
-
-async fn init_global_data() {
-	API_KEY
-		.set(
-			var("API_KEY")
-				.expect_error("Missing `API_KEY` env var, please include this in your .env file"),
-		)
-		.expect_error("API_KEY can only be initialized once");
-
-	let mongo_url = var("MONGO_URL")
-		.expect_error("Missing `MONGO_URL` env var, please include this in your .env file");
-	let options = ClientOptions::parse(mongo_url)
+static DB_POOL: OnceCell<SqlitePool> = OnceCell::const_new();
+static USERS_DB_POOL: OnceCell<SqlitePool> = OnceCell::const_new();
+
+async fn init_global_data(config: &Config) {
+	HYPIXEL_CLIENT
+		.set(HypixelClient::new(
+			&config.api_key,
+			&config.hypixel_api_url,
+			config.key_limit,
+		))
+		.expect_error("HYPIXEL_CLIENT can only be initialized once");
+
+	ELITE_CLIENT
+		.set(EliteClient::new(&config.elite_api_url))
+		.expect_error("ELITE_CLIENT can only be initialized once");
+
+	let options = ClientOptions::parse(&config.mongo_url)
 		.await
 		.expect_error("Could not create mongo client options");
 	let client = Client::with_options(options).expect_error("Could not create mongo client");
@@ -53,15 +72,19 @@ async fn init_global_data() {
 		.expect_error("MONGO_CLIENT can only be initialized once");
 
 	ERROR_COLOR
-		.set(0x770505)
+		.set(config.error_color)
 		.expect_error("ERROR_COLOR can only be initialized once");
 
-	let manager = SqliteConnectionManager::file("src/data/tags.db");
-	let pool = Pool::new(manager).expect_error("Failed to create connection pool");
-
+	let pool = create_pool(&config.tags_db()).expect_error("Failed to create connection pool");
 	DB_POOL
 		.set(pool)
 		.expect_error("DB_POOL can only be initialized once");
+
+	let users_pool =
+		create_pool(&config.users_db()).expect_error("Failed to create users connection pool");
+	USERS_DB_POOL
+		.set(users_pool)
+		.expect_error("USERS_DB_POOL can only be initialized once");
 }
 
 async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
@@ -104,10 +127,25 @@ impl<T, E: std::fmt::Debug> ExpectError<T> for Result<T, E> {
 async fn main() {
 	dotenv().ok();
 
+	let config = Config::load().unwrap_or_else(|e| {
+		println!("\x1b[31;1m[ERROR] Invalid config:\x1b[0m\n{}", e);
+		std::process::exit(1);
+	});
+	for dir in config.db_dirs() {
+		std::fs::create_dir_all(&dir).expect_error(&format!(
+			"Failed to create data directory {}",
+			dir.display()
+		));
+	}
+	CONFIG
+		.set(config)
+		.expect_error("CONFIG can only be initialized once");
+	let config = config::config();
+
 	let options = poise::FrameworkOptions {
 		commands: commands::get_all_commands(),
 		prefix_options: poise::PrefixFrameworkOptions {
-			prefix: Some("cow ".into()),
+			prefix: Some(config.prefix.clone()),
 			edit_tracker: Some(Arc::new(poise::EditTracker::for_timespan(
 				Duration::from_secs(3600),
 			))),
@@ -117,6 +155,11 @@ async fn main() {
 		pre_command: |ctx| {
 			Box::pin(async move {
 				println!("[COMMAND] started {}", ctx.command().qualified_name);
+
+				let author = ctx.author();
+				if let Err(e) = refresh_username(author.id.get(), &author.name).await {
+					println!("\x1b[31;1m[ERROR] Failed to refresh username:\x1b[0m {}", e);
+				}
 			})
 		},
 		post_command: |ctx| {
@@ -238,14 +281,54 @@ async fn main() {
 		..Default::default()
 	};
 
-	init_global_data().await;
-	create_users_table().expect_error("Failed to create database \'users\'");
+	init_global_data(config).await;
+	migrate_users_db()
+		.await
+		.expect_error("Failed to migrate database \'users\'");
+	migrate_tags_db()
+		.await
+		.expect_error("Failed to migrate database \'tags\'");
+
+	let log_channel = config.log_channel.map(serenity::ChannelId::new);
+	let supervisor = Arc::new(Supervisor::new(log_channel));
 
+	let task_supervisor = supervisor.clone();
 	let framework = poise::Framework::builder()
 		.setup(move |ctx, _ready, framework| {
 			Box::pin(async move {
 				println!("Logged in as {}", _ready.user.name);
 				poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+
+				task_supervisor.spawn("Uptime tracker", ctx.http.clone(), |shutdown| {
+					uptime_updater(
+						HYPIXEL_CLIENT.get().unwrap(),
+						uptime_collection(MONGO_CLIENT.get().unwrap()),
+						shutdown,
+					)
+				});
+
+				task_supervisor.spawn("Gains tracker", ctx.http.clone(), |shutdown| {
+					gains_updater(
+						ELITE_CLIENT.get().unwrap(),
+						gains_collection(MONGO_CLIENT.get().unwrap()),
+						shutdown,
+					)
+				});
+
+				let http = ctx.http.clone();
+				task_supervisor.spawn(
+					"Gexp requirement checker",
+					ctx.http.clone(),
+					move |shutdown| requirement_checker(http.clone(), shutdown),
+				);
+
+				let http = ctx.http.clone();
+				task_supervisor.spawn(
+					"Linked account refresher",
+					ctx.http.clone(),
+					move |shutdown| link_refresher(http.clone(), shutdown),
+				);
+
 				Ok(Data {
 					tag_db: Arc::new(TagDb),
 				})
@@ -254,28 +337,25 @@ async fn main() {
 		.options(options)
 		.build();
 
-	let client = MONGO_CLIENT.get().unwrap();
-	tokio::task::spawn_blocking(move || {
-		if let Err(err) = tokio::runtime::Handle::current().block_on(uptime_updater(
-			&API_KEY.get().unwrap(),
-			client.database("Players").collection("Uptime"),
-		)) {
-			eprintln!(
-				"\x1b[31;1m[ERROR] Error in uptime tracker:\x1b[0m\n\n{:?}",
-				err
-			);
-		}
-	});
-
-	let token = var("BOT_TOKEN").expect_error(
-		"\x1b[31;1m[ERROR] Missing `BOT_TOKEN` env var, please include this in your .env file",
-	);
 	let intents =
 		serenity::GatewayIntents::non_privileged() | serenity::GatewayIntents::MESSAGE_CONTENT;
 
-	let client = serenity::ClientBuilder::new(token, intents)
+	let mut client = serenity::ClientBuilder::new(&config.bot_token, intents)
 		.framework(framework)
-		.await;
+		.await
+		.expect_error("Failed to create discord client");
+
+	let shard_manager = client.shard_manager.clone();
+	tokio::spawn(async move {
+		wait_for_signal().await;
+		println!("\x1b[34m[INFO] Shutting down\x1b[0m");
+		shard_manager.shutdown_all().await;
+	});
+
+	if let Err(e) = client.start().await {
+		println!("\x1b[31;1m[ERROR] Client stopped:\x1b[0m {}", e);
+	}
 
-	client.unwrap().start().await.unwrap();
+	// background tasks finish their current write before the runtime is dropped
+	supervisor.shutdown().await;
 }
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use futures::stream::{self, StreamExt};
+use serenity::all::{ChannelId, CreateEmbed, CreateMessage, Http};
+
+use crate::commands::uptime_utils::get_uptime_totals;
+use crate::commands::utils::get_mojang_info;
+use crate::config::config;
+use crate::data::database::{
+	GuildRequirement,
+	get_guild_requirements,
+	set_guild_requirement_last_report,
+};
+use crate::hypixel::client::GuildLookup;
+use crate::hypixel::rate_limit::Priority;
+use crate::tasks::supervisor::Shutdown;
+use crate::tasks::update_uptime::{ApiError, update_guild_uptime};
+use crate::{HYPIXEL_CLIENT, MONGO_CLIENT};
+
+const REPORT_COLOR: u32 = 0x2b2d31;
+// leave room for the footer line below the 4096 character description limit
+const REPORT_DESCRIPTION_LIMIT: usize = 3_900;
+
+pub async fn requirement_checker(
+	http: Arc<Http>,
+	mut shutdown: Shutdown,
+) -> Result<(), ApiError> {
+	let interval = config().requirement_interval as i64;
+
+	loop {
+		let now = Utc::now().timestamp();
+		let requirements = get_guild_requirements()
+			.await
+			.map_err(|e| ApiError::Api(e.to_string()))?;
+
+		for requirement in requirements {
+			if shutdown.is_triggered() {
+				return Ok(());
+			}
+			if now - requirement.last_report < interval * 3600 {
+				continue;
+			}
+
+			let embed = match create_requirement_report(&requirement).await {
+				| Ok(embed) => embed,
+				| Err(e) => {
+					println!(
+						"\x1b[31;1m[ERROR] Failed to create gexp report for {}:\x1b[0m {}",
+						requirement.hypixel_guild_name, e
+					);
+					continue;
+				},
+			};
+
+			if let Err(e) = ChannelId::new(requirement.channel_id)
+				.send_message(&http, CreateMessage::new().embed(embed))
+				.await
+			{
+				println!(
+					"\x1b[31;1m[ERROR] Failed to send gexp report for {}:\x1b[0m {}",
+					requirement.hypixel_guild_name, e
+				);
+				continue;
+			}
+
+			set_guild_requirement_last_report(requirement.discord_guild_id, now)
+				.await
+				.map_err(|e| ApiError::Api(e.to_string()))?;
+		}
+
+		if !shutdown.sleep(Duration::from_secs(3600)).await {
+			return Ok(());
+		}
+	}
+}
+
+/// List every member of the guild that earned less than the required gexp in the last 7 days
+pub async fn create_requirement_report(
+	requirement: &GuildRequirement
+) -> Result<CreateEmbed, ApiError> {
+	let hypixel = HYPIXEL_CLIENT
+		.get()
+		.expect("HYPIXEL_CLIENT is uninitialized");
+	let client = MONGO_CLIENT.get().expect("MONGO_CLIENT is uninitialized");
+
+	let guild = update_guild_uptime(
+		GuildLookup::Id(&requirement.hypixel_guild_id),
+		hypixel,
+		client,
+		Priority::Background,
+	)
+	.await?;
+	let totals = get_uptime_totals(7, Some(&guild.id), None).await?;
+
+	let gexp_by_uuid: HashMap<String, i64> = totals
+		.into_iter()
+		.map(|total| (total.uuid, total.gexp))
+		.collect();
+	let member_count = guild.members.len();
+	let mut below_requirement: Vec<(String, i64)> = guild
+		.members
+		.into_iter()
+		.map(|uuid| {
+			let gexp = gexp_by_uuid.get(&uuid).copied().unwrap_or(0);
+			(uuid, gexp)
+		})
+		.filter(|(_, gexp)| *gexp < requirement.gexp)
+		.collect();
+	below_requirement.sort_by_key(|(_, gexp)| *gexp);
+
+	let uuids: Vec<String> = below_requirement
+		.iter()
+		.map(|(uuid, _)| uuid.clone())
+		.collect();
+	let names: Vec<String> = stream::iter(uuids)
+		.map(|uuid| async move {
+			get_mojang_info(uuid.clone())
+				.await
+				.map(|(name, _)| name)
+				.unwrap_or(uuid)
+		})
+		.buffered(10)
+		.collect()
+		.await;
+
+	let mut description = if below_requirement.is_empty() {
+		"Every member meets the requirement".to_string()
+	} else {
+		format!(
+			"{} of {} members are below {} gexp\n\n",
+			below_requirement.len(),
+			member_count,
+			requirement.gexp
+		)
+	};
+
+	for (index, ((_, gexp), name)) in below_requirement.iter().zip(names).enumerate() {
+		let line = format!("**{}**: {} gexp\n", name.replace("_", "\\_"), gexp);
+		if description.len() + line.len() > REPORT_DESCRIPTION_LIMIT {
+			description.push_str(&format!("...and {} more", below_requirement.len() - index));
+			break;
+		}
+		description.push_str(&line);
+	}
+
+	Ok(CreateEmbed::default()
+		.title(format!("Weekly gexp report for {}", guild.name))
+		.description(description)
+		.color(REPORT_COLOR))
+}
@@ -1 +1,6 @@
+pub mod gexp_requirements;
+pub mod refresh_links;
+pub mod supervisor;
+pub mod tracker_status;
+pub mod update_gains;
 pub mod update_uptime;
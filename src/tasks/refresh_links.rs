@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use serenity::all::{Http, UserId};
+
+use crate::HYPIXEL_CLIENT;
+use crate::commands::utils::{DiscordIdentity, check_hypixel_linked_account, get_mojang_info};
+use crate::config::config;
+use crate::data::database::{LinkedAccount, get_stale_linked_accounts, save_linked_account_check};
+use crate::hypixel::rate_limit::Priority;
+use crate::tasks::supervisor::Shutdown;
+use crate::tasks::update_uptime::ApiError;
+
+/// accounts checked per hour, keeps mojang and hypixel requests spread out
+const REFRESH_BATCH_SIZE: usize = 200;
+
+/// Re-resolve the ign of linked accounts and make sure hypixel still links them to the same
+/// discord user. Links that fail are flagged, never removed
+pub async fn link_refresher(
+	http: Arc<Http>,
+	mut shutdown: Shutdown,
+) -> Result<(), ApiError> {
+	let max_age = config().link_refresh_interval as i64 * 3600;
+
+	loop {
+		let accounts = get_stale_linked_accounts(max_age, REFRESH_BATCH_SIZE)
+			.await
+			.map_err(|e| ApiError::Api(e.to_string()))?;
+
+		let (mut renamed, mut flagged) = (0u32, 0u32);
+		for (account, discord_user) in &accounts {
+			if shutdown.is_triggered() {
+				return Ok(());
+			}
+
+			let (username, flag_reason) = check_account(&http, account, discord_user).await;
+			flagged += flag_reason.is_some() as u32;
+			if save_linked_account_check(account.user_id, &account.mc_uuid, &username, flag_reason)
+				.await
+				.map_err(|e| ApiError::Api(e.to_string()))?
+			{
+				renamed += 1;
+			}
+		}
+
+		if !accounts.is_empty() {
+			println!(
+				"\x1b[34m[INFO] Checked {} linked accounts, {} renamed, {} flagged\x1b[0m",
+				accounts.len(),
+				renamed,
+				flagged
+			);
+		}
+
+		if !shutdown.sleep(Duration::from_secs(3600)).await {
+			return Ok(());
+		}
+	}
+}
+
+/// The account's current ign and why its link should be flagged, if it should be.
+/// Anything that can't be looked up right now keeps its previous value
+async fn check_account(
+	http: &Http,
+	account: &LinkedAccount,
+	discord_user: &str,
+) -> (String, Option<String>) {
+	let username = match get_mojang_info(account.mc_uuid.clone()).await {
+		| Ok((username, _)) => username,
+		| Err(e) => {
+			println!(
+				"\x1b[31;1m[ERROR] Failed to look up ign of {}:\x1b[0m {}",
+				account.mc_uuid, e
+			);
+			account.mc_username.clone()
+		},
+	};
+
+	// forced links were never confirmed by hypixel, so there is nothing to re-check
+	if account.verified_at.is_none() {
+		return (username, account.flag_reason.clone());
+	}
+
+	let hypixel = HYPIXEL_CLIENT
+		.get()
+		.expect("HYPIXEL_CLIENT is uninitialized");
+	// the stored username misses the display name and legacy tag the link may have matched
+	let identity = match UserId::new(account.user_id).to_user(http).await {
+		| Ok(user) => DiscordIdentity::from(&user),
+		| Err(_) => DiscordIdentity::from_username(discord_user),
+	};
+	let flag_reason = match check_hypixel_linked_account(
+		hypixel,
+		&account.mc_uuid,
+		&identity,
+		Priority::Background,
+	)
+	.await
+	{
+		| Ok(result) => result.err().map(|mismatch| mismatch.to_string()),
+		| Err(e) => {
+			println!(
+				"\x1b[31;1m[ERROR] Failed to check hypixel socials of {}:\x1b[0m {}",
+				account.mc_uuid, e
+			);
+			account.flag_reason.clone()
+		},
+	};
+
+	(username, flag_reason)
+}
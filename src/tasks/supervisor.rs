@@ -0,0 +1,294 @@
+use std::any::Any;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::FutureExt;
+use serenity::all::{ChannelId, CreateEmbed, CreateMessage, Http};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+
+use crate::ERROR_COLOR;
+use crate::tasks::update_uptime::ApiError;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(15 * 60);
+/// a task that ran this long before failing starts again from the minimum backoff
+const HEALTHY_RUNTIME: Duration = Duration::from_secs(60 * 60);
+/// how long tasks get to finish their current write after a shutdown signal
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Handed to background tasks so they can stop at a point where no write is in flight
+#[derive(Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+	pub fn is_triggered(&self) -> bool { *self.0.borrow() }
+
+	pub async fn triggered(&mut self) {
+		// the sender lives in the supervisor, if it is gone the bot is exiting anyway
+		let _ = self.0.wait_for(|triggered| *triggered).await;
+	}
+
+	/// Sleep for `duration`, returns false if a shutdown was triggered in the meantime
+	pub async fn sleep(
+		&mut self,
+		duration: Duration,
+	) -> bool {
+		tokio::select! {
+			_ = tokio::time::sleep(duration) => true,
+			_ = self.triggered() => false,
+		}
+	}
+}
+
+/// Runs background jobs, restarting them with backoff when they fail
+pub struct Supervisor {
+	shutdown:    watch::Sender<bool>,
+	tasks:       Mutex<JoinSet<()>>,
+	log_channel: Option<ChannelId>,
+}
+
+impl Supervisor {
+	pub fn new(log_channel: Option<ChannelId>) -> Self {
+		Supervisor {
+			shutdown: watch::Sender::new(false),
+			tasks: Mutex::new(JoinSet::new()),
+			log_channel,
+		}
+	}
+
+	pub fn shutdown_signal(&self) -> Shutdown { Shutdown(self.shutdown.subscribe()) }
+
+	/// Keep `job` running until shutdown, a job returning `Ok` is treated as finished
+	pub fn spawn<F, Fut>(
+		&self,
+		name: &'static str,
+		http: Arc<Http>,
+		job: F,
+	) where
+		F: Fn(Shutdown) -> Fut + Send + 'static,
+		Fut: Future<Output = Result<(), ApiError>> + Send + 'static,
+	{
+		let mut shutdown = self.shutdown_signal();
+		let log_channel = self.log_channel;
+
+		self.tasks.lock().unwrap().spawn(async move {
+			let mut backoff = MIN_BACKOFF;
+
+			loop {
+				let started = Instant::now();
+				// the job runs inside this task so aborting it on shutdown stops the job too,
+				// a panic is caught and reported instead of killing the supervisor
+				let error = match AssertUnwindSafe(job(shutdown.clone())).catch_unwind().await {
+					| Ok(Ok(())) => return,
+					| Ok(Err(e)) => e.to_string(),
+					| Err(panic) => format!("Task panicked: {}", panic_message(&*panic)),
+				};
+
+				if shutdown.is_triggered() {
+					return;
+				}
+
+				if started.elapsed() >= HEALTHY_RUNTIME {
+					backoff = MIN_BACKOFF;
+				}
+
+				println!(
+					"\x1b[31;1m[ERROR] {} crashed, restarting in {}s:\x1b[0m {}",
+					name,
+					backoff.as_secs(),
+					error
+				);
+				report_crash(&http, log_channel, name, &error, backoff).await;
+
+				if !shutdown.sleep(backoff).await {
+					return;
+				}
+				backoff = (backoff * 2).min(MAX_BACKOFF);
+			}
+		});
+	}
+
+	/// Tell every task to stop and wait for them to finish their current work
+	pub async fn shutdown(&self) {
+		self.shutdown.send_replace(true);
+
+		let mut tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+		if tokio::time::timeout(SHUTDOWN_TIMEOUT, async {
+			while tasks.join_next().await.is_some() {}
+		})
+		.await
+		.is_err()
+		{
+			println!(
+				"\x1b[31;1m[ERROR] Background tasks did not stop within {}s, aborting them\x1b[0m",
+				SHUTDOWN_TIMEOUT.as_secs()
+			);
+			tasks.abort_all();
+		}
+	}
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+	if let Some(message) = panic.downcast_ref::<&str>() {
+		message
+	} else if let Some(message) = panic.downcast_ref::<String>() {
+		message
+	} else {
+		"unknown panic"
+	}
+}
+
+async fn report_crash(
+	http: &Http,
+	log_channel: Option<ChannelId>,
+	name: &str,
+	error: &str,
+	backoff: Duration,
+) {
+	let Some(channel) = log_channel else {
+		return;
+	};
+
+	let embed = CreateEmbed::default()
+		.title(format!("{} crashed", name))
+		.description(format!(
+			"```\n{}\n```\nRestarting in {}s",
+			error.replace("```", "'''"),
+			backoff.as_secs()
+		))
+		.color(*ERROR_COLOR.get().unwrap_or(&0x770505));
+
+	if let Err(e) = channel
+		.send_message(http, CreateMessage::new().embed(embed))
+		.await
+	{
+		println!(
+			"\x1b[31;1m[ERROR] Failed to report crash to log channel:\x1b[0m {}",
+			e
+		);
+	}
+}
+
+/// Resolves once the process receives SIGINT or SIGTERM
+pub async fn wait_for_signal() {
+	#[cfg(unix)]
+	{
+		use tokio::signal::unix::{SignalKind, signal};
+
+		let mut sigterm = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+		tokio::select! {
+			_ = tokio::signal::ctrl_c() => {},
+			_ = sigterm.recv() => {},
+		}
+	}
+
+	#[cfg(not(unix))]
+	{
+		let _ = tokio::signal::ctrl_c().await;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::atomic::{AtomicU32, Ordering};
+
+	use super::*;
+
+	#[tokio::test(start_paused = true)]
+	async fn restarts_failed_jobs_until_they_finish() {
+		let supervisor = Supervisor::new(None);
+		let runs = Arc::new(AtomicU32::new(0));
+
+		let counter = runs.clone();
+		supervisor.spawn("test job", Arc::new(Http::new("")), move |_| {
+			let counter = counter.clone();
+			async move {
+				if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+					Err(ApiError::Api("failed".to_string()))
+				} else {
+					Ok(())
+				}
+			}
+		});
+
+		tokio::time::sleep(MIN_BACKOFF * 4).await;
+		assert_eq!(runs.load(Ordering::SeqCst), 3);
+		supervisor.shutdown().await;
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn shutdown_waits_for_jobs_to_stop() {
+		let supervisor = Supervisor::new(None);
+		let stopped = Arc::new(AtomicU32::new(0));
+
+		let counter = stopped.clone();
+		supervisor.spawn("test job", Arc::new(Http::new("")), move |mut shutdown| {
+			let counter = counter.clone();
+			async move {
+				while shutdown.sleep(Duration::from_secs(60)).await {}
+				// stands in for a write finishing after the signal
+				tokio::time::sleep(Duration::from_secs(1)).await;
+				counter.fetch_add(1, Ordering::SeqCst);
+				Ok(())
+			}
+		});
+
+		tokio::task::yield_now().await;
+		supervisor.shutdown().await;
+		assert_eq!(stopped.load(Ordering::SeqCst), 1);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn restarts_jobs_that_panic() {
+		let supervisor = Supervisor::new(None);
+		let runs = Arc::new(AtomicU32::new(0));
+
+		let counter = runs.clone();
+		supervisor.spawn("test job", Arc::new(Http::new("")), move |_| {
+			let counter = counter.clone();
+			async move {
+				if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+					panic!("job panicked");
+				}
+				Ok(())
+			}
+		});
+
+		tokio::time::sleep(MIN_BACKOFF * 2).await;
+		assert_eq!(runs.load(Ordering::SeqCst), 2);
+		supervisor.shutdown().await;
+	}
+
+	/// Counts how often the future holding it was dropped
+	struct DropCounter(Arc<AtomicU32>);
+
+	impl Drop for DropCounter {
+		fn drop(&mut self) { self.0.fetch_add(1, Ordering::SeqCst); }
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn shutdown_aborts_jobs_that_ignore_it() {
+		let supervisor = Supervisor::new(None);
+		let dropped = Arc::new(AtomicU32::new(0));
+
+		let counter = dropped.clone();
+		supervisor.spawn("test job", Arc::new(Http::new("")), move |_| {
+			let guard = DropCounter(counter.clone());
+			async move {
+				let _guard = guard;
+				std::future::pending::<()>().await;
+				Ok(())
+			}
+		});
+
+		tokio::task::yield_now().await;
+		supervisor.shutdown().await;
+		// aborting only polls the task one last time, let it run
+		tokio::task::yield_now().await;
+		assert_eq!(dropped.load(Ordering::SeqCst), 1);
+	}
+}
@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+
+use crate::tasks::update_uptime::ApiError;
+
+/// Progress of the uptime updater, shared with `/tracker status`
+pub static TRACKER_STATUS: Mutex<TrackerStatus> = Mutex::new(TrackerStatus::new());
+
+#[derive(Debug, Clone)]
+pub struct TrackerStatus {
+	pub cycle: u64,
+	pub running: bool,
+	pub cycle_started: Option<DateTime<Utc>>,
+	pub players_total: usize,
+	pub guilds_total: usize,
+	pub players_processed: usize,
+	pub guilds_processed: usize,
+	pub requests: u32,
+	/// failures this cycle, keyed by `ApiError` variant
+	pub failures: BTreeMap<&'static str, u32>,
+	pub last_success: Option<DateTime<Utc>>,
+	pub next_run: Option<DateTime<Utc>>,
+}
+
+impl TrackerStatus {
+	const fn new() -> Self {
+		TrackerStatus {
+			cycle: 0,
+			running: false,
+			cycle_started: None,
+			players_total: 0,
+			guilds_total: 0,
+			players_processed: 0,
+			guilds_processed: 0,
+			requests: 0,
+			failures: BTreeMap::new(),
+			last_success: None,
+			next_run: None,
+		}
+	}
+}
+
+/// Copy of the current status, so the lock isn't held while building a reply
+pub fn tracker_status() -> TrackerStatus { TRACKER_STATUS.lock().unwrap().clone() }
+
+pub fn update_tracker_status(update: impl FnOnce(&mut TrackerStatus)) {
+	update(&mut TRACKER_STATUS.lock().unwrap());
+}
+
+pub fn record_failure(error: &ApiError) {
+	update_tracker_status(|status| *status.failures.entry(error.kind()).or_insert(0) += 1);
+}
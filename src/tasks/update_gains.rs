@@ -0,0 +1,228 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use bson::oid::ObjectId;
+use bson::{DateTime as BsonDateTime, Document, doc};
+use chrono::{NaiveDate, TimeZone, Utc};
+use chrono_tz::America::New_York;
+use mongodb::options::{IndexOptions, UpdateOneModel};
+use mongodb::{Client, Collection, IndexModel};
+use serde::{Deserialize, Serialize};
+
+use crate::MONGO_CLIENT;
+use crate::config::config;
+use crate::data::tracking::get_tracked_players;
+use crate::elite::client::EliteClient;
+use crate::elite::responses::{CollectionsDataPoint, SkillsDataPoint};
+use crate::tasks::supervisor::Shutdown;
+use crate::tasks::update_uptime::ApiError;
+
+/// days of graph data requested per update, anything missed while the bot was down is backfilled
+const GAINS_HISTORY_DAYS: i64 = 8;
+
+/// Collection and skill totals at the end of a day, gains are the difference between two days
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GainsSnapshot {
+	#[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+	pub id:          Option<ObjectId>,
+	pub uuid:        String,
+	pub date:        BsonDateTime,
+	#[serde(default)]
+	pub collections: HashMap<String, i64>,
+	/// total xp per skill
+	#[serde(default)]
+	pub skills:      HashMap<String, f64>,
+}
+
+pub fn gains_collection(client: &Client) -> Collection<GainsSnapshot> {
+	let config = config();
+	config.database(client).collection(&config.gains_collection)
+}
+
+pub async fn gains_updater(
+	elite: &EliteClient,
+	collection: Collection<GainsSnapshot>,
+	mut shutdown: Shutdown,
+) -> Result<(), ApiError> {
+	let sleep = config().gains_interval;
+
+	let client = MONGO_CLIENT.get().unwrap();
+	let index_model = IndexModel::builder()
+		.keys(doc! { "uuid": 1, "date": 1 })
+		.options(IndexOptions::builder().unique(true).build())
+		.build();
+	collection.create_index(index_model).await?;
+
+	loop {
+		let players = get_tracked_players(client).await?;
+		println!("Updating gains for {} players", players.len());
+
+		let mut updated = 0u32;
+		for player in players {
+			// only stop between players so a bulk write is never cut off
+			if shutdown.is_triggered() {
+				return Ok(());
+			}
+
+			match update_gains(&player.uuid, elite, client).await {
+				| Ok(true) => updated += 1,
+				| Ok(false) => {},
+				| Err(e) => println!(
+					"\x1b[31;1m[ERROR] Failed to update gains for {}:\x1b[0m {}",
+					player.uuid, e
+				),
+			}
+		}
+
+		println!(
+			"\x1b[34m[INFO] Updated gains for {} players\x1b[0m",
+			updated
+		);
+
+		if !shutdown.sleep(Duration::from_secs(sleep * 3600)).await {
+			return Ok(());
+		}
+	}
+}
+
+/// Store daily snapshots for a player's selected profile, returns false if they have no profile
+pub async fn update_gains(
+	uuid: &str,
+	elite: &EliteClient,
+	client: &Client,
+) -> Result<bool, ApiError> {
+	let Some(profile_id) = elite.get_selected_profile(uuid).await? else {
+		return Ok(false);
+	};
+
+	let from = (Utc::now() - chrono::Duration::days(GAINS_HISTORY_DAYS)).timestamp();
+	let collections = elite
+		.get_collection_graph(uuid, &profile_id, from, GAINS_HISTORY_DAYS)
+		.await?;
+	let skills = elite
+		.get_skill_graph(uuid, &profile_id, from, GAINS_HISTORY_DAYS)
+		.await?;
+
+	let snapshots = daily_snapshots(collections, skills);
+	if snapshots.is_empty() {
+		return Ok(true);
+	}
+
+	let collection = gains_collection(client);
+	let models: Vec<_> = snapshots
+		.into_iter()
+		.map(|(day, (collections, skills))| {
+			let mut set = Document::new();
+			if let Some(collections) = collections {
+				set.insert("collections", bson::to_bson(&collections).unwrap());
+			}
+			if let Some(skills) = skills {
+				set.insert("skills", bson::to_bson(&skills).unwrap());
+			}
+
+			UpdateOneModel::builder()
+				.namespace(collection.namespace())
+				.filter(doc! { "uuid": uuid, "date": day_to_bson(day) })
+				.update(doc! { "$set": set })
+				.upsert(true)
+				.build()
+		})
+		.collect();
+
+	client.bulk_write(models).await?;
+	Ok(true)
+}
+
+type DailySnapshot = (Option<HashMap<String, i64>>, Option<HashMap<String, f64>>);
+
+/// Keep the last data point of every day, days are in Hypixel's timezone like uptime
+fn daily_snapshots(
+	collections: Vec<CollectionsDataPoint>,
+	skills: Vec<SkillsDataPoint>,
+) -> BTreeMap<NaiveDate, DailySnapshot> {
+	let mut latest_collections: BTreeMap<NaiveDate, CollectionsDataPoint> = BTreeMap::new();
+	for point in collections {
+		let day = timestamp_to_day(point.timestamp);
+		if latest_collections
+			.get(&day)
+			.is_none_or(|latest| latest.timestamp <= point.timestamp)
+		{
+			latest_collections.insert(day, point);
+		}
+	}
+
+	let mut latest_skills: BTreeMap<NaiveDate, SkillsDataPoint> = BTreeMap::new();
+	for point in skills {
+		let day = timestamp_to_day(point.timestamp);
+		if latest_skills
+			.get(&day)
+			.is_none_or(|latest| latest.timestamp <= point.timestamp)
+		{
+			latest_skills.insert(day, point);
+		}
+	}
+
+	let mut snapshots: BTreeMap<NaiveDate, DailySnapshot> = BTreeMap::new();
+	for (day, point) in latest_collections {
+		snapshots.entry(day).or_default().0 = Some(point.collections);
+	}
+	for (day, point) in latest_skills {
+		snapshots.entry(day).or_default().1 = Some(point.skills);
+	}
+	snapshots
+}
+
+fn timestamp_to_day(timestamp: i64) -> NaiveDate {
+	New_York
+		.timestamp_opt(timestamp, 0)
+		.single()
+		.unwrap_or_else(|| New_York.from_utc_datetime(&Utc::now().naive_utc()))
+		.date_naive()
+}
+
+/// Midnight of `day` in Hypixel's timezone, the same dates uptime is stored with
+pub fn day_to_bson(day: NaiveDate) -> BsonDateTime {
+	let midnight = New_York
+		.from_local_datetime(&day.and_hms_opt(0, 0, 0).unwrap())
+		.earliest()
+		.unwrap();
+	BsonDateTime::from_chrono(midnight.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn keeps_the_last_point_of_each_day() {
+		// 2024-01-01 10:00 and 20:00 in New York, then 2024-01-02 10:00
+		let collections = vec![
+			CollectionsDataPoint {
+				timestamp:   1_704_121_200,
+				collections: HashMap::from([("WHEAT".to_string(), 100)]),
+			},
+			CollectionsDataPoint {
+				timestamp:   1_704_157_200,
+				collections: HashMap::from([("WHEAT".to_string(), 150)]),
+			},
+			CollectionsDataPoint {
+				timestamp:   1_704_207_600,
+				collections: HashMap::from([("WHEAT".to_string(), 400)]),
+			},
+		];
+		let skills = vec![SkillsDataPoint {
+			timestamp: 1_704_207_600,
+			skills:    HashMap::from([("farming".to_string(), 1_000.0)]),
+		}];
+
+		let snapshots = daily_snapshots(collections, skills);
+		let first = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+		let second = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+
+		assert_eq!(snapshots.len(), 2);
+		assert_eq!(snapshots[&first].0.as_ref().unwrap()["WHEAT"], 150);
+		assert!(snapshots[&first].1.is_none());
+		assert_eq!(snapshots[&second].0.as_ref().unwrap()["WHEAT"], 400);
+		assert_eq!(snapshots[&second].1.as_ref().unwrap()["farming"], 1_000.0);
+	}
+}
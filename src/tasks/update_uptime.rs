@@ -1,20 +1,35 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::env::var;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::future::Future;
 use std::time::Duration;
 
 use bson::oid::ObjectId;
 use bson::{DateTime as BsonDateTime, Document, doc};
-use chrono::{NaiveDateTime, TimeZone, Utc};
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
 use chrono_tz::America::New_York;
-use dotenv::dotenv;
-use mongodb::options::{IndexOptions, ReplaceOneModel};
+use futures::stream::TryStreamExt;
+use mongodb::options::{IndexOptions, ReplaceOneModel, UpdateOneModel};
 use mongodb::{Client, Collection, IndexModel};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use crate::MONGO_CLIENT;
+use crate::config::config;
+use crate::data::tracking::{
+	TrackedPlayer,
+	create_tracking_indexes,
+	get_tracked_guilds,
+	get_tracked_players,
+	remove_expired,
+	tracked_guilds,
+	tracked_players,
+};
+use crate::elite::client::EliteError;
+use crate::hypixel::client::{GuildLookup, HypixelClient, HypixelError};
+use crate::hypixel::rate_limit::Priority;
+use crate::hypixel::responses::Guild;
+use crate::tasks::supervisor::Shutdown;
+use crate::tasks::tracker_status::{record_failure, update_tracker_status};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Uptime {
@@ -22,74 +37,433 @@ pub struct Uptime {
 	pub id:       Option<ObjectId>,
 	pub uuid:     String,
 	pub gexp:     i64,
+	/// midnight of `day` in Hypixel's timezone
 	pub date:     BsonDateTime,
+	/// `YYYY-MM-DD` in Hypixel's timezone, the key every read should use
+	pub day:      String,
 	pub guild_id: String,
 }
 
+const DAY_FORMAT: &str = "%Y-%m-%d";
+
+/// Today in Hypixel's timezone, which is what gexp history is keyed by
+pub fn hypixel_today() -> NaiveDate {
+	New_York
+		.from_utc_datetime(&Utc::now().naive_utc())
+		.date_naive()
+}
+
+pub fn day_key(day: NaiveDate) -> String { day.format(DAY_FORMAT).to_string() }
+
+pub fn uptime_collection(client: &Client) -> Collection<Uptime> {
+	let config = config();
+	config
+		.database(client)
+		.collection(&config.uptime_collection)
+}
+
+/// Day key of the first day in a window of `time_window` days ending today
+pub fn window_start_day(time_window: i64) -> String {
+	day_key(hypixel_today() - chrono::Duration::days(time_window - 1))
+}
+
 pub async fn uptime_updater(
-	api_key: &str,
+	hypixel: &HypixelClient,
 	collection: Collection<Uptime>,
+	mut shutdown: Shutdown,
 ) -> Result<(), ApiError> {
-	dotenv().ok();
+	let sleep = config().update_interval;
 
-	let sleep = var("UPDATE_INTERVAL")
-		.unwrap_or("10".to_string())
-		.parse::<u64>()
-		.unwrap();
-	let key_limit = var("KEY_LIMIT")
-		.unwrap_or("300".to_string())
-		.parse::<u64>()
-		.unwrap();
+	let client = MONGO_CLIENT.get().unwrap();
+	migrate_uptime_days(&collection).await?;
+	create_uptime_indexes(&collection).await?;
+	create_tracking_indexes(client).await?;
+	seed_tracked_players(client, &collection).await?;
 
 	loop {
-		let client = &MONGO_CLIENT.get().unwrap();
+		let removed = remove_expired(client).await?;
+		if removed > 0 {
+			println!(
+				"\x1b[34m[INFO] Stopped tracking {} expired players and guilds\x1b[0m",
+				removed
+			);
+		}
 
-		let players: Vec<String> = collection
-			.distinct("uuid", Document::new())
+		let players: Vec<String> = get_tracked_players(client)
+			.await?
+			.into_iter()
+			.map(|player| player.uuid)
+			.collect();
+		let tracked_guild_ids: Vec<String> = get_tracked_guilds(client)
 			.await?
 			.into_iter()
-			.filter_map(|bson_value| bson_value.as_str().map(ToOwned::to_owned))
+			.map(|guild| guild.guild_id)
 			.collect();
 
-		println!("Updating Uptime for {} players", players.len());
-		let mut processed_uuids = Vec::with_capacity(players.len());
+		// guilds tracked players were seen in recently are fetched by id too, one response covers
+		// every member
+		let recent = window_start_day(3);
+		let recent_guild_ids = collection
+			.distinct(
+				"guild_id",
+				doc! { "uuid": { "$in": players.clone() }, "day": { "$gte": recent } },
+			)
+			.await?;
+		let guild_ids = merge_guild_ids(
+			tracked_guild_ids,
+			recent_guild_ids
+				.iter()
+				.filter_map(|bson_value| bson_value.as_str()),
+		);
+
+		println!(
+			"Updating Uptime for {} players in {} known guilds",
+			players.len(),
+			guild_ids.len()
+		);
+
+		update_tracker_status(|status| {
+			status.cycle += 1;
+			status.running = true;
+			status.cycle_started = Some(Utc::now());
+			status.players_total = players.len();
+			status.guilds_total = guild_ids.len();
+			status.players_processed = 0;
+			status.guilds_processed = 0;
+			status.requests = 0;
+			status.failures.clear();
+			status.next_run = None;
+		});
+
+		let pass = run_update_pass(players, guild_ids, &shutdown, |target| async move {
+			update_guild_uptime(target.guild_lookup(), hypixel, client, Priority::Background).await
+		})
+		.await;
+		let Some(pass) = pass else {
+			return Ok(());
+		};
+
+		println!(
+			"\x1b[34m[INFO] Updated {} guilds with {} requests\x1b[0m",
+			pass.processed_guilds.len(),
+			pass.requests
+		);
+
+		if pass.no_guild > 0 {
+			println!(
+				"\x1b[34m[INFO] {} players are no longer in a guild\x1b[0m",
+				pass.no_guild
+			);
+		}
+
+		update_tracker_status(|status| {
+			status.running = false;
+			status.last_success = Some(Utc::now());
+			status.next_run = Some(Utc::now() + chrono::Duration::minutes(sleep as i64));
+		});
 
-		let mut no_guild = 0u16;
-		for player in players {
-			// sleep between updates to avoid rate limiting
-			tokio::time::sleep(Duration::from_secs(sleep / (4 * key_limit / 300))).await;
+		if !shutdown.sleep(Duration::from_secs(sleep * 60)).await {
+			return Ok(());
+		}
+	}
+}
 
-			if processed_uuids.contains(&player) {
-				continue;
-			}
+/// Tracked guilds first, then guilds tracked players were recently seen in, without duplicates
+fn merge_guild_ids<'a>(
+	mut tracked: Vec<String>,
+	recent: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+	for guild_id in recent {
+		if !tracked.iter().any(|id| id == guild_id) {
+			tracked.push(guild_id.to_owned());
+		}
+	}
+	tracked
+}
 
-			if let Err(ApiError::NoGuild()) = update_uptime(&player, api_key, client).await {
-				no_guild += 1;
-			}
+/// One guild or player request made by `run_update_pass`
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum UpdateTarget {
+	Guild(String),
+	Player(String),
+}
 
-			processed_uuids.push(player);
+impl UpdateTarget {
+	fn guild_lookup(&self) -> GuildLookup<'_> {
+		match self {
+			| UpdateTarget::Guild(guild_id) => GuildLookup::Id(guild_id),
+			| UpdateTarget::Player(uuid) => GuildLookup::Player(uuid),
 		}
+	}
+}
 
-		if no_guild > 0 {
-			println!(
-				"\x1b[34m[INFO] {} players are no longer in a guild\x1b[0m",
-				no_guild
-			);
+#[derive(Debug, Default)]
+struct PassSummary {
+	processed_guilds: HashSet<String>,
+	requests:         u32,
+	no_guild:         u16,
+}
+
+/// Update every guild, then every player that wasn't a member of one of them. Failures are
+/// recorded and skipped, `None` means the pass stopped early for a shutdown
+async fn run_update_pass<F, Fut>(
+	players: Vec<String>,
+	guild_ids: Vec<String>,
+	shutdown: &Shutdown,
+	mut update: F,
+) -> Option<PassSummary>
+where
+	F: FnMut(UpdateTarget) -> Fut,
+	Fut: Future<Output = Result<GuildInfo, ApiError>>,
+{
+	let mut processed_uuids: HashSet<String> = HashSet::with_capacity(players.len());
+	let mut pass = PassSummary::default();
+
+	for guild_id in guild_ids {
+		// only stop between requests so a guild's bulk write is never cut off
+		if shutdown.is_triggered() {
+			return None;
 		}
 
-		tokio::time::sleep(Duration::from_secs(sleep * 60)).await;
+		pass.requests += 1;
+		match update(UpdateTarget::Guild(guild_id.clone())).await {
+			| Ok(guild_info) => {
+				processed_uuids.extend(guild_info.members);
+				pass.processed_guilds.insert(guild_info.id);
+			},
+			// disbanded, its old members are looked up by player below
+			| Err(ApiError::NoGuild()) => {},
+			| Err(e) => {
+				println!(
+					"\x1b[31;1m[ERROR] Failed to update guild {}:\x1b[0m {}",
+					guild_id, e
+				);
+				record_failure(&e);
+			},
+		}
+
+		update_tracker_status(|status| {
+			status.players_processed = processed_uuids.len();
+			status.guilds_processed = pass.processed_guilds.len();
+			status.requests = pass.requests;
+		});
+	}
+
+	for player in players {
+		if processed_uuids.contains(&player) {
+			continue;
+		}
+		if shutdown.is_triggered() {
+			return None;
+		}
+
+		pass.requests += 1;
+		match update(UpdateTarget::Player(player.clone())).await {
+			| Ok(guild_info) => {
+				processed_uuids.extend(guild_info.members);
+				pass.processed_guilds.insert(guild_info.id);
+			},
+			| Err(ApiError::NoGuild()) => pass.no_guild += 1,
+			| Err(e) => {
+				println!(
+					"\x1b[31;1m[ERROR] Failed to update uptime for {}:\x1b[0m {}",
+					player, e
+				);
+				record_failure(&e);
+			},
+		}
+
+		processed_uuids.insert(player);
+		update_tracker_status(|status| {
+			status.players_processed = processed_uuids.len();
+			status.guilds_processed = pass.processed_guilds.len();
+			status.requests = pass.requests;
+		});
+	}
+
+	Some(pass)
+}
+
+/// Before subscriptions existed every player with uptime was tracked, carry them over once
+async fn seed_tracked_players(
+	client: &Client,
+	collection: &Collection<Uptime>,
+) -> Result<(), ApiError> {
+	if tracked_players(client).estimated_document_count().await? > 0
+		|| tracked_guilds(client).estimated_document_count().await? > 0
+	{
+		return Ok(());
+	}
+
+	let now = BsonDateTime::now();
+	let players: Vec<TrackedPlayer> = collection
+		.distinct("uuid", Document::new())
+		.await?
+		.into_iter()
+		.filter_map(|bson_value| bson_value.as_str().map(ToOwned::to_owned))
+		.map(|uuid| TrackedPlayer {
+			id: None,
+			uuid,
+			reason: "Tracked before subscriptions were added".to_string(),
+			requested_by: "unknown".to_string(),
+			added_at: now,
+			expires_at: None,
+		})
+		.collect();
+
+	if !players.is_empty() {
+		println!(
+			"\x1b[34m[INFO] Tracking {} players that already have uptime\x1b[0m",
+			players.len()
+		);
+		tracked_players(client).insert_many(players).await?;
+	}
+
+	Ok(())
+}
+
+async fn create_uptime_indexes(collection: &Collection<Uptime>) -> Result<(), ApiError> {
+	let index_model = IndexModel::builder()
+		.keys(doc! { "uuid": 1, "day": 1 })
+		.options(IndexOptions::builder().unique(true).build())
+		.build();
+	collection.create_index(index_model).await?;
+	Ok(())
+}
+
+/// Uptime stored before `day` existed, only read by `migrate_uptime_days`
+#[derive(Debug, Deserialize)]
+struct LegacyUptime {
+	#[serde(rename = "_id")]
+	id:   ObjectId,
+	uuid: String,
+	gexp: i64,
+	date: BsonDateTime,
+	day:  Option<String>,
+}
+
+/// Give every document a `day` key and merge documents that were stored twice for one day
+///
+/// Older versions keyed uptime by a UTC timestamp, which shifted by an hour across DST and
+/// left two documents for the same day. The one with the most gexp is kept.
+async fn migrate_uptime_days(collection: &Collection<Uptime>) -> Result<(), ApiError> {
+	let legacy = collection
+		.count_documents(doc! { "day": { "$exists": false } })
+		.await?;
+	if legacy == 0 {
+		return Ok(());
+	}
+
+	println!(
+		"\x1b[34m[INFO] Migrating {} uptime documents to day keys\x1b[0m",
+		legacy
+	);
+
+	// the old unique index would reject moving a document onto a date its duplicate had
+	if let Err(e) = collection.drop_index("uuid_1_date_1").await {
+		println!("\x1b[34m[INFO] No old uptime index to drop:\x1b[0m {}", e);
+	}
+
+	let documents: Vec<LegacyUptime> = collection
+		.clone_with_type::<LegacyUptime>()
+		.find(Document::new())
+		.await?
+		.try_collect()
+		.await?;
+	let (updates, deletes) = plan_day_merges(documents);
+
+	let client = MONGO_CLIENT.get().unwrap();
+	let update_count = updates.len();
+	let models: Vec<_> = updates
+		.into_iter()
+		.map(|(id, day)| {
+			UpdateOneModel::builder()
+				.namespace(collection.namespace())
+				.filter(doc! { "_id": id })
+				.update(doc! { "$set": { "date": est_date_to_bson(&day), "day": day } })
+				.build()
+		})
+		.collect();
+
+	if !deletes.is_empty() {
+		collection
+			.delete_many(doc! { "_id": { "$in": &deletes } })
+			.await?;
+	}
+	if !models.is_empty() {
+		client.bulk_write(models).await?;
 	}
+
+	println!(
+		"\x1b[34m[INFO] Keyed {} uptime documents by day and removed {} duplicates\x1b[0m",
+		update_count,
+		deletes.len()
+	);
+	Ok(())
+}
+
+/// Returns the documents to key by day and the duplicates to delete
+fn plan_day_merges(documents: Vec<LegacyUptime>) -> (Vec<(ObjectId, String)>, Vec<ObjectId>) {
+	let mut days: HashMap<(String, String), Vec<LegacyUptime>> = HashMap::new();
+	for document in documents {
+		let day = document
+			.day
+			.clone()
+			.unwrap_or_else(|| legacy_day(document.date));
+		days.entry((document.uuid.clone(), day))
+			.or_default()
+			.push(document);
+	}
+
+	let mut updates = Vec::new();
+	let mut deletes = Vec::new();
+	for ((_, day), mut duplicates) in days {
+		// keep the most gexp, preferring a document that is already keyed by day
+		duplicates.sort_by_key(|document| (document.gexp, document.day.is_some()));
+		let kept = duplicates.pop().unwrap();
+
+		deletes.extend(duplicates.into_iter().map(|document| document.id));
+		if kept.day.as_deref() != Some(&day) || kept.date != est_date_to_bson(&day) {
+			updates.push((kept.id, day));
+		}
+	}
+
+	(updates, deletes)
+}
+
+/// Day a legacy timestamp belongs to, rounded to the nearest midnight in Hypixel's timezone
+///
+/// Old documents were stored at midnight in either UTC or Hypixel's timezone, rounding
+/// handles both
+fn legacy_day(date: BsonDateTime) -> String {
+	let local = New_York.from_utc_datetime(&date.to_chrono().naive_utc());
+	day_key((local + chrono::Duration::hours(12)).date_naive())
 }
 
 #[derive(Debug)]
 pub enum ApiError {
 	Database(mongodb::error::Error),
+	Hypixel(HypixelError),
+	Elite(EliteError),
 	Api(String),
 	NoGuild(),
 }
 
 impl std::error::Error for ApiError {}
 
+impl ApiError {
+	/// Name of the variant, used to group failures in the tracker status
+	pub fn kind(&self) -> &'static str {
+		match self {
+			| ApiError::Database(_) => "Database",
+			| ApiError::Hypixel(_) => "Hypixel",
+			| ApiError::Elite(_) => "Elite",
+			| ApiError::Api(_) => "Api",
+			| ApiError::NoGuild() => "NoGuild",
+		}
+	}
+}
+
 impl fmt::Display for ApiError {
 	fn fmt(
 		&self,
@@ -97,6 +471,8 @@ impl fmt::Display for ApiError {
 	) -> fmt::Result {
 		match self {
 			| ApiError::Database(e) => write!(f, "Database error: {}", e),
+			| ApiError::Hypixel(e) => write!(f, "{}", e),
+			| ApiError::Elite(e) => write!(f, "{}", e),
 			| ApiError::Api(msg) => write!(f, "{}", msg),
 			| ApiError::NoGuild() => write!(f, "Player is not in a guild"),
 		}
@@ -107,115 +483,114 @@ impl From<mongodb::error::Error> for ApiError {
 	fn from(err: mongodb::error::Error) -> ApiError { ApiError::Database(err) }
 }
 
-impl From<Box<dyn std::error::Error + Send + Sync>> for ApiError {
-	fn from(e: Box<dyn std::error::Error + Send + Sync>) -> ApiError {
-		ApiError::Api(e.to_string())
-	}
+impl From<HypixelError> for ApiError {
+	fn from(err: HypixelError) -> ApiError { ApiError::Hypixel(err) }
 }
 
-#[derive(Deserialize)]
-struct GuildResponse {
-	success: bool,
-	guild:   Option<Guild>,
-	#[serde(default)]
-	cause:   Option<String>,
-}
-
-#[derive(Deserialize)]
-struct Guild {
-	members: Vec<Member>,
-	#[serde(rename = "_id")]
-	id:      String,
+impl From<EliteError> for ApiError {
+	fn from(err: EliteError) -> ApiError { ApiError::Elite(err) }
 }
 
-#[derive(Deserialize)]
-#[allow(non_snake_case)]
-struct Member {
-	uuid:       String,
-	expHistory: Option<Value>,
+impl From<Box<dyn std::error::Error + Send + Sync>> for ApiError {
+	fn from(e: Box<dyn std::error::Error + Send + Sync>) -> ApiError {
+		match e.downcast::<ApiError>() {
+			| Ok(e) => *e,
+			| Err(e) => ApiError::Api(e.to_string()),
+		}
+	}
 }
 
 type UptimeHistory = HashMap<String, i64>;
 type GuildUptimeData = HashMap<String, UptimeHistory>;
 
-async fn get_guild_uptime_data(
-	api_key: &str,
-	uuid: &str,
-) -> Result<(String, GuildUptimeData), Box<dyn std::error::Error + Send + Sync>> {
-	let url = format!("https://api.hypixel.net/v2/guild?key={api_key}&player={uuid}");
-
-	let response = reqwest::get(&url).await?;
-	let response_text = response.text().await?;
-	let guild_response: GuildResponse = serde_json::from_str(&response_text)?;
-
-	if !guild_response.success {
-		if let Some(e) = &guild_response.cause {
-			return Err(Box::new(ApiError::Api(e.clone())));
-		}
-	}
+pub struct GuildInfo {
+	pub id:      String,
+	pub name:    String,
+	pub members: Vec<String>,
+}
 
-	let guild = guild_response.guild.ok_or_else(|| ApiError::NoGuild())?;
+fn get_guild_uptime_data(guild: Guild) -> (GuildInfo, GuildUptimeData) {
 	let mut guild_uptime_data = HashMap::with_capacity(guild.members.len());
+	let mut members = Vec::with_capacity(guild.members.len());
 
 	for member in guild.members {
-		if let Some(exp_history) = member.expHistory {
-			let uptime_history: UptimeHistory = exp_history
-				.as_object()
-				.map(|history| {
-					history
-						.iter()
-						.filter_map(|(date, xp)| {
-							xp.as_i64().map(|xp_value| (date.to_owned(), xp_value))
-						})
-						.collect()
-				})
-				.unwrap_or_default();
-
-			if !uptime_history.is_empty() {
-				guild_uptime_data.insert(member.uuid, uptime_history);
-			}
+		members.push(member.uuid.clone());
+
+		if !member.exp_history.is_empty() {
+			guild_uptime_data.insert(member.uuid, member.exp_history);
 		}
 	}
 
-	Ok((guild.id, guild_uptime_data))
+	let guild_info = GuildInfo {
+		id: guild.id,
+		name: guild.name,
+		members,
+	};
+
+	(guild_info, guild_uptime_data)
 }
 
 pub async fn update_uptime(
 	uuid: &str,
-	api_key: &str,
+	hypixel: &HypixelClient,
 	client: &Client,
+	priority: Priority,
 ) -> Result<(), ApiError> {
-	let (guild_id, member_uptime_history) = get_guild_uptime_data(api_key, uuid).await?;
+	update_guild_uptime(GuildLookup::Player(uuid), hypixel, client, priority).await?;
+	Ok(())
+}
 
-	let collection: Collection<Uptime> = client.database("Players").collection("Uptime");
-	let index_model = IndexModel::builder()
-		.keys(doc! { "uuid": 1, "date": 1 })
-		.options(IndexOptions::builder().unique(true).build())
-		.build();
-	collection.create_index(index_model).await?;
+async fn fetch_guild_uptime(
+	lookup: GuildLookup<'_>,
+	hypixel: &HypixelClient,
+	priority: Priority,
+) -> Result<(GuildInfo, GuildUptimeData), ApiError> {
+	let guild = hypixel
+		.get_guild(lookup, priority)
+		.await?
+		.ok_or(ApiError::NoGuild())?;
+	Ok(get_guild_uptime_data(guild))
+}
+
+/// Convert a date from Hypixel's `expHistory` to midnight in Hypixel's timezone
+fn est_date_to_bson(unformatted_date: &str) -> BsonDateTime {
+	let date = format!("{} 00:00:00", unformatted_date);
+	let naive_date =
+		NaiveDateTime::parse_from_str(&date, "%Y-%m-%d %H:%M:%S").expect("Failed to parse date");
+	let est_date = New_York.from_local_datetime(&naive_date).unwrap();
+	BsonDateTime::from_chrono(est_date.with_timezone(&Utc))
+}
+
+/// Fetch a guild and store the uptime history of all of its members
+pub async fn update_guild_uptime(
+	lookup: GuildLookup<'_>,
+	hypixel: &HypixelClient,
+	client: &Client,
+	priority: Priority,
+) -> Result<GuildInfo, ApiError> {
+	let (guild_info, member_uptime_history) = fetch_guild_uptime(lookup, hypixel, priority).await?;
+	let guild_id = &guild_info.id;
+
+	let collection = uptime_collection(client);
 
 	let models: Vec<_> = member_uptime_history
 		.into_iter()
 		.flat_map(|(uuid, uptime_history)| {
-			let guild_id = Cow::Borrowed(&guild_id);
+			let guild_id = Cow::Borrowed(guild_id);
 			uptime_history.into_iter().map({
 				let value = collection.clone();
-				move |(unformatted_date, new_gexp)| {
-					let date = format!("{} 00:00:00", unformatted_date);
-					let naive_date = NaiveDateTime::parse_from_str(&date, "%Y-%m-%d %H:%M:%S")
-						.expect("Failed to parse date");
-					let est_date = New_York.from_local_datetime(&naive_date).unwrap();
-					let bson_date = BsonDateTime::from_chrono(est_date.with_timezone(&Utc));
-
+				// Hypixel already keys gexp history by `YYYY-MM-DD` in its own timezone
+				move |(day, new_gexp)| {
 					let filter = doc! {
 						"uuid": &uuid,
-						"date": &bson_date,
+						"day": &day,
 					};
 
 					let update = doc! {
 						"uuid": &uuid,
 						"gexp": new_gexp,
-						"date": bson_date,
+						"date": est_date_to_bson(&day),
+						"day": day,
 						"guild_id": guild_id.as_ref(),
 					};
 
@@ -234,5 +609,259 @@ pub async fn update_uptime(
 		client.bulk_write(models).await?;
 	}
 
-	Ok(())
+	Ok(guild_info)
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+	use wiremock::matchers::{method, path, query_param};
+	use wiremock::{Mock, MockServer, ResponseTemplate};
+
+	use super::*;
+	use crate::tasks::supervisor::Supervisor;
+
+	#[tokio::test]
+	async fn fetches_uptime_for_every_guild_member() {
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/v2/guild"))
+			.and(query_param("player", "player1"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+				"success": true,
+				"guild": {
+					"_id": "guild1",
+					"name": "Cow Guild",
+					"members": [
+						{ "uuid": "player1", "expHistory": { "2025-03-01": 9000, "2025-02-28": 0 } },
+						{ "uuid": "player2", "expHistory": { "2025-03-01": 150 } },
+						{ "uuid": "player3" }
+					]
+				}
+			})))
+			.mount(&server)
+			.await;
+
+		let hypixel = HypixelClient::new("test-key", &server.uri(), 300);
+		let (guild_info, uptime_data) = fetch_guild_uptime(
+			GuildLookup::Player("player1"),
+			&hypixel,
+			Priority::Background,
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(guild_info.id, "guild1");
+		assert_eq!(guild_info.members, ["player1", "player2", "player3"]);
+		assert_eq!(uptime_data.len(), 2);
+		assert_eq!(uptime_data["player1"]["2025-03-01"], 9000);
+		assert_eq!(uptime_data["player2"]["2025-03-01"], 150);
+	}
+
+	#[tokio::test]
+	async fn player_without_guild_is_no_guild() {
+		let server = MockServer::start().await;
+		Mock::given(path("/v2/guild"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+				"success": true,
+				"guild": null
+			})))
+			.mount(&server)
+			.await;
+
+		let hypixel = HypixelClient::new("test-key", &server.uri(), 300);
+		let result = fetch_guild_uptime(
+			GuildLookup::Player("player1"),
+			&hypixel,
+			Priority::Background,
+		)
+		.await;
+
+		assert!(matches!(result, Err(ApiError::NoGuild())));
+	}
+
+	#[tokio::test]
+	async fn invalid_key_is_reported() {
+		let server = MockServer::start().await;
+		Mock::given(path("/v2/guild"))
+			.respond_with(ResponseTemplate::new(403).set_body_json(json!({
+				"success": false,
+				"cause": "Invalid API key"
+			})))
+			.mount(&server)
+			.await;
+
+		let hypixel = HypixelClient::new("bad-key", &server.uri(), 300);
+		let result = fetch_guild_uptime(
+			GuildLookup::Player("player1"),
+			&hypixel,
+			Priority::Background,
+		)
+		.await;
+
+		assert!(matches!(
+			result,
+			Err(ApiError::Hypixel(HypixelError::InvalidKey))
+		));
+	}
+
+	fn guild_response(guild: serde_json::Value) -> ResponseTemplate {
+		ResponseTemplate::new(200).set_body_json(json!({ "success": true, "guild": guild }))
+	}
+
+	#[tokio::test]
+	async fn update_pass_fetches_guilds_then_remaining_players() {
+		let server = MockServer::start().await;
+		Mock::given(path("/v2/guild"))
+			.and(query_param("id", "guild1"))
+			.respond_with(guild_response(json!({
+				"_id": "guild1",
+				"name": "Cow Guild",
+				"members": [
+					{ "uuid": "player1", "expHistory": { "2025-03-01": 9000 } },
+					{ "uuid": "player2", "expHistory": { "2025-03-01": 150 } }
+				]
+			})))
+			.mount(&server)
+			.await;
+		Mock::given(path("/v2/guild"))
+			.and(query_param("id", "disbanded"))
+			.respond_with(guild_response(json!(null)))
+			.mount(&server)
+			.await;
+		Mock::given(path("/v2/guild"))
+			.and(query_param("player", "player3"))
+			.respond_with(guild_response(json!(null)))
+			.mount(&server)
+			.await;
+		Mock::given(path("/v2/guild"))
+			.and(query_param("player", "player4"))
+			.respond_with(ResponseTemplate::new(403).set_body_json(json!({
+				"success": false,
+				"cause": "Invalid API key"
+			})))
+			.mount(&server)
+			.await;
+
+		let hypixel = HypixelClient::new("test-key", &server.uri(), 300);
+		let shutdown = Supervisor::new(None).shutdown_signal();
+		let guild_ids = merge_guild_ids(vec!["guild1".to_string()], ["disbanded", "guild1"]);
+		let players = ["player1", "player4", "player3"].map(String::from).to_vec();
+
+		let mut targets = Vec::new();
+		let pass = run_update_pass(players, guild_ids, &shutdown, |target| {
+			targets.push(target.clone());
+			let hypixel = &hypixel;
+			async move {
+				fetch_guild_uptime(target.guild_lookup(), hypixel, Priority::Background)
+					.await
+					.map(|(guild_info, _)| guild_info)
+			}
+		})
+		.await
+		.unwrap();
+
+		// player1 came with guild1, the failing player4 doesn't stop player3 from being looked up
+		assert_eq!(
+			targets,
+			[
+				UpdateTarget::Guild("guild1".to_string()),
+				UpdateTarget::Guild("disbanded".to_string()),
+				UpdateTarget::Player("player4".to_string()),
+				UpdateTarget::Player("player3".to_string()),
+			]
+		);
+		assert_eq!(pass.processed_guilds, HashSet::from(["guild1".to_string()]));
+		assert_eq!(pass.requests, 4);
+		assert_eq!(pass.no_guild, 1);
+	}
+
+	#[tokio::test]
+	async fn update_pass_stops_on_shutdown() {
+		let supervisor = Supervisor::new(None);
+		let shutdown = supervisor.shutdown_signal();
+		supervisor.shutdown().await;
+
+		let mut requests = 0;
+		let pass = run_update_pass(
+			vec!["player1".to_string()],
+			vec!["guild1".to_string()],
+			&shutdown,
+			|_| {
+				requests += 1;
+				async { Err(ApiError::NoGuild()) }
+			},
+		)
+		.await;
+
+		assert!(pass.is_none());
+		assert_eq!(requests, 0);
+	}
+
+	fn legacy(
+		id: u8,
+		gexp: i64,
+		date: &str,
+		day: Option<&str>,
+	) -> LegacyUptime {
+		LegacyUptime {
+			id: ObjectId::from_bytes([id; 12]),
+			uuid: "player1".to_string(),
+			gexp,
+			date: BsonDateTime::parse_rfc3339_str(date).unwrap(),
+			day: day.map(ToOwned::to_owned),
+		}
+	}
+
+	#[test]
+	fn legacy_dates_round_to_hypixel_days() {
+		// midnight in New York and midnight in UTC both belong to the same day
+		assert_eq!(
+			legacy_day(BsonDateTime::parse_rfc3339_str("2025-03-09T05:00:00Z").unwrap()),
+			"2025-03-09"
+		);
+		assert_eq!(
+			legacy_day(BsonDateTime::parse_rfc3339_str("2025-03-09T00:00:00Z").unwrap()),
+			"2025-03-09"
+		);
+		// after the DST switch midnight is an hour earlier in UTC
+		assert_eq!(
+			legacy_day(BsonDateTime::parse_rfc3339_str("2025-03-10T04:00:00Z").unwrap()),
+			"2025-03-10"
+		);
+	}
+
+	#[test]
+	fn duplicate_days_keep_the_most_gexp() {
+		let documents = vec![
+			legacy(1, 4_000, "2025-03-10T05:00:00Z", None),
+			legacy(2, 9_000, "2025-03-10T04:00:00Z", None),
+			legacy(3, 150, "2025-03-11T04:00:00Z", Some("2025-03-11")),
+		];
+
+		let (updates, deletes) = plan_day_merges(documents);
+
+		assert_eq!(
+			updates,
+			vec![(ObjectId::from_bytes([2; 12]), "2025-03-10".to_string())]
+		);
+		assert_eq!(deletes, vec![ObjectId::from_bytes([1; 12])]);
+	}
+
+	#[test]
+	fn est_dates_are_stored_as_utc() {
+		// EST is UTC-5 in winter and UTC-4 in summer
+		assert_eq!(
+			est_date_to_bson("2025-01-15")
+				.try_to_rfc3339_string()
+				.unwrap(),
+			"2025-01-15T05:00:00Z"
+		);
+		assert_eq!(
+			est_date_to_bson("2025-07-15")
+				.try_to_rfc3339_string()
+				.unwrap(),
+			"2025-07-15T04:00:00Z"
+		);
+	}
 }